@@ -0,0 +1,163 @@
+//! 比较 `mgrep::search` 和逐行 `str::contains` 的吞吐量。
+//!
+//! `cargo bench --bench search` 在几十 MB 的生成语料上测量；
+//! 不带 `--bench` 运行时（例如 `cargo test --benches`）只用很小的语料检查结果一致。
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use learning_rust::mgrep;
+
+/// 可复现的伪随机数（xorshift）
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.next() as usize % items.len()]
+    }
+}
+
+const WORDS: &[&str] = &[
+    "the", "of", "and", "to", "in", "is", "you", "that", "it", "he", "was", "for", "on", "are",
+    "as", "with", "his", "they", "at", "be", "this", "have", "from", "or", "one", "had", "by",
+    "word", "but", "not", "what", "all", "were", "when", "we", "there", "can", "an", "your",
+];
+
+const CODE: &[&str] = &[
+    "let",
+    "mut",
+    "fn",
+    "pub",
+    "struct",
+    "impl",
+    "self",
+    "Self",
+    "->",
+    "=>",
+    "match",
+    "Some(x)",
+    "None",
+    "Ok(())",
+    "return",
+    "&str",
+    "Vec<u8>",
+    "usize",
+    "if",
+    "else",
+    "for",
+    "in",
+    "{",
+    "}",
+    "(",
+    ");",
+    "//",
+    "0",
+    "1",
+    "i += 1;",
+    "config.paths",
+    "String::new()",
+];
+
+/// 生成约 `size` 字节的文本，每行 `words_per_line` 个词，大约每 `every` 行插入一次 `needle`
+fn corpus(size: usize, words: &[&str], words_per_line: usize, needle: &str, every: u64) -> String {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    let mut text = String::with_capacity(size + 256);
+    while text.len() < size {
+        for i in 0..words_per_line {
+            if i > 0 {
+                text.push(' ');
+            }
+            text.push_str(rng.pick(words));
+        }
+        if rng.next().is_multiple_of(every) {
+            text.push(' ');
+            text.push_str(needle);
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn naive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|x| x.contains(query)).collect()
+}
+
+/// 重复运行直到超过 `budget`，返回最快的一次
+fn measure<F: FnMut() -> usize>(budget: Duration, mut f: F) -> Duration {
+    let started = Instant::now();
+    let mut best = Duration::MAX;
+    while started.elapsed() < budget || best == Duration::MAX {
+        let t = Instant::now();
+        black_box(f());
+        best = best.min(t.elapsed());
+    }
+    best
+}
+
+fn main() {
+    let full = std::env::args().any(|arg| arg == "--bench");
+    let (size, budget) = if full {
+        (64 << 20, Duration::from_secs(2))
+    } else {
+        (64 << 10, Duration::ZERO)
+    };
+
+    let cases = [
+        (
+            "prose, rare hit",
+            corpus(size, WORDS, 12, "Sherlock", 10_000),
+            "Sherlock",
+        ),
+        (
+            "prose, no hit",
+            corpus(size, WORDS, 12, "Sherlock", 10_000),
+            "Moriarty",
+        ),
+        (
+            "prose, common hit",
+            corpus(size, WORDS, 12, "Sherlock", 3),
+            "Sherlock",
+        ),
+        (
+            "code, rare hit",
+            corpus(size, CODE, 8, "unsafe {", 5_000),
+            "unsafe {",
+        ),
+        ("prose, one byte", corpus(size, WORDS, 12, "Z", 1_000), "Z"),
+        (
+            "prose, common bytes",
+            corpus(size, WORDS, 12, "the the", 1_000),
+            "the the",
+        ),
+    ];
+
+    println!(
+        "{:<22} {:>10} {:>12} {:>12} {:>8}",
+        "corpus", "lines", "naive MB/s", "search MB/s", "speedup"
+    );
+    for (name, text, query) in &cases {
+        let expected = naive(query, text);
+        assert_eq!(expected, mgrep::search(query, text), "{}", name);
+
+        let naive_time = measure(budget, || naive(query, text).len());
+        let search_time = measure(budget, || mgrep::search(query, text).len());
+        let mb = text.len() as f64 / (1 << 20) as f64;
+        println!(
+            "{:<22} {:>10} {:>12.0} {:>12.0} {:>7.1}x",
+            name,
+            expected.len(),
+            mb / naive_time.as_secs_f64(),
+            mb / search_time.as_secs_f64(),
+            naive_time.as_secs_f64() / search_time.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! 比较 `tpool::ThreadPool`（work-stealing）和原来所有 worker 共用一个
+//! `Arc<Mutex<mpsc::Receiver>>` 的线程池的吞吐量。
+//!
+//! `cargo bench --bench tpool` 每种情况跑几十万个 job；
+//! 不带 `--bench` 运行时（例如 `cargo test --benches`）只跑很少的 job 检查结果一致。
+
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use learning_rust::tpool::ThreadPool;
+
+/// 原来的实现：worker 抢同一把锁，并且在持有锁的时候阻塞在 `recv()` 上
+mod baseline {
+    use std::{
+        sync::{mpsc, Arc, Mutex},
+        thread,
+    };
+
+    type Job = Box<dyn FnOnce() + Send + 'static>;
+
+    pub struct MutexPool {
+        sender: mpsc::Sender<Job>,
+    }
+
+    impl MutexPool {
+        pub fn new(size: usize) -> MutexPool {
+            let (sender, receiver) = mpsc::channel::<Job>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..size {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                });
+            }
+            MutexPool { sender }
+        }
+
+        pub fn execute<F: FnOnce() + Send + 'static>(&self, f: F) {
+            self.sender.send(Box::new(f)).unwrap();
+        }
+    }
+}
+
+use baseline::MutexPool;
+
+/// 两种线程池共同的接口；线程池要在 job 里继续提交 job，所以都是 `'static` 的
+trait Pool: Sync + 'static {
+    fn submit(&'static self, job: Box<dyn FnOnce() + Send + 'static>);
+}
+
+impl Pool for ThreadPool {
+    fn submit(&'static self, job: Box<dyn FnOnce() + Send + 'static>) {
+        self.execute(job);
+    }
+}
+
+impl Pool for MutexPool {
+    fn submit(&'static self, job: Box<dyn FnOnce() + Send + 'static>) {
+        self.execute(job);
+    }
+}
+
+/// 所有 job 都执行完时通知主线程
+struct Latch {
+    left: AtomicUsize,
+    done: Mutex<mpsc::Sender<()>>,
+}
+
+impl Latch {
+    fn new(count: usize) -> (Arc<Latch>, mpsc::Receiver<()>) {
+        let (tx, rx) = mpsc::channel();
+        let latch = Latch {
+            left: AtomicUsize::new(count),
+            done: Mutex::new(tx),
+        };
+        (Arc::new(latch), rx)
+    }
+
+    fn count_down(&self) {
+        if self.left.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.done.lock().unwrap().send(()).unwrap();
+        }
+    }
+}
+
+/// 每个 job 大约做 `work` 次迭代的计算
+fn busy(work: u64) -> u64 {
+    let mut x = 0u64;
+    for i in 0..work {
+        x = black_box(x.wrapping_mul(31).wrapping_add(i));
+    }
+    x
+}
+
+/// 从线程池外面提交 `jobs` 个 job
+fn flat<P: Pool>(pool: &'static P, jobs: usize, work: u64) {
+    let (latch, done) = Latch::new(jobs);
+    for _ in 0..jobs {
+        let latch = Arc::clone(&latch);
+        pool.submit(Box::new(move || {
+            black_box(busy(work));
+            latch.count_down();
+        }));
+    }
+    done.recv().unwrap();
+}
+
+/// 每个 job 再提交两个子 job，深度为 `depth` 的二叉树，共 `2^(depth+1) - 1` 个 job
+fn tree<P: Pool>(pool: &'static P, depth: u32, work: u64) {
+    fn node<P: Pool>(pool: &'static P, depth: u32, work: u64, latch: Arc<Latch>) {
+        black_box(busy(work));
+        if depth > 0 {
+            for _ in 0..2 {
+                let latch = Arc::clone(&latch);
+                pool.submit(Box::new(move || node(pool, depth - 1, work, latch)));
+            }
+        }
+        latch.count_down();
+    }
+
+    let (latch, done) = Latch::new((1 << (depth + 1)) - 1);
+    pool.submit(Box::new(move || node(pool, depth, work, latch)));
+    done.recv().unwrap();
+}
+
+/// 跑一遍某种情况，`main` 里对两种线程池各构造一个
+type Run = Box<dyn Fn()>;
+
+/// 重复运行直到超过 `budget`，返回最快的一次
+fn measure<F: FnMut()>(budget: Duration, mut f: F) -> Duration {
+    let started = Instant::now();
+    let mut best = Duration::MAX;
+    while started.elapsed() < budget || best == Duration::MAX {
+        let t = Instant::now();
+        f();
+        best = best.min(t.elapsed());
+    }
+    best
+}
+
+fn main() {
+    let full = std::env::args().any(|arg| arg == "--bench");
+    let (scale, budget) = if full {
+        (1, Duration::from_secs(2))
+    } else {
+        (64, Duration::ZERO)
+    };
+    let threads = thread::available_parallelism().map_or(4, |n| n.get());
+
+    // job 里会用到线程池，让它们一直活到进程结束
+    let stealing: &'static ThreadPool = Box::leak(Box::new(ThreadPool::new(threads)));
+    let mutex: &'static MutexPool = Box::leak(Box::new(MutexPool::new(threads)));
+
+    let cases: [(&str, usize, u64, bool); 4] = [
+        ("flat, empty jobs", 200_000 / scale, 0, false),
+        ("flat, 2k iterations", 100_000 / scale, 2_000, false),
+        ("nested tree, empty", (1 << 18) / scale, 0, true),
+        ("nested tree, 2k iterations", (1 << 17) / scale, 2_000, true),
+    ];
+
+    println!("{} threads", threads);
+    println!(
+        "{:<28} {:>9} {:>14} {:>14} {:>8}",
+        "case", "jobs", "mutex jobs/s", "steal jobs/s", "speedup"
+    );
+    for (name, jobs, work, nested) in cases {
+        let (jobs, run_mutex, run_stealing): (usize, Run, Run) = if nested {
+            let depth = jobs.max(2).ilog2() - 1;
+            (
+                (1 << (depth + 1)) - 1,
+                Box::new(move || tree(mutex, depth, work)),
+                Box::new(move || tree(stealing, depth, work)),
+            )
+        } else {
+            (
+                jobs,
+                Box::new(move || flat(mutex, jobs, work)),
+                Box::new(move || flat(stealing, jobs, work)),
+            )
+        };
+        let mutex_time = measure(budget, &run_mutex);
+        let stealing_time = measure(budget, &run_stealing);
+        let rate = |t: Duration| jobs as f64 / t.as_secs_f64();
+        println!(
+            "{:<28} {:>9} {:>14.0} {:>14.0} {:>7.1}x",
+            name,
+            jobs,
+            rate(mutex_time),
+            rate(stealing_time),
+            mutex_time.as_secs_f64() / stealing_time.as_secs_f64()
+        );
+    }
+}
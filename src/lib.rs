@@ -8,177 +8,6 @@ fn internal_adder(a: i32, b: i32) -> i32 {
     a + b
 }
 
-pub mod mgrep {
-    use std::{
-        env::{self, Args},
-        error::Error,
-        fs,
-    };
+pub mod mgrep;
 
-    #[derive(Debug)]
-    pub struct Config {
-        pub query: String,
-        pub filename: String,
-        pub case_sensitive: bool,
-    }
-
-    impl Config {
-        pub fn new(mut args: Args) -> Result<Config, &'static str> {
-            if args.len() < 3 {
-                return Err("Not enough arguments");
-            }
-            args.next();
-
-            let query = match args.next() {
-                Some(arg) => arg,
-                None => return Err("Didn't get a query string"),
-            };
-
-            let filename = match args.next() {
-                Some(arg) => arg,
-                None => return Err("Didn't get a file name"),
-            };
-
-            let case_sensitive = env::var("CASE_INSENSITIVE").is_err();
-
-            Ok(Config {
-                query,
-                filename,
-                case_sensitive,
-            })
-        }
-    }
-
-    pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-        let contents = fs::read_to_string(config.filename)?;
-
-        let results = if config.case_sensitive {
-            search(&config.query, &contents)
-        } else {
-            search_case_insensitive(&config.query, &contents)
-        };
-
-        for line in results {
-            println!("match: {}", line);
-        }
-
-        Ok(())
-    }
-
-    pub fn search<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
-        contents.lines().filter(|x| x.contains(query)).collect()
-    }
-
-    pub fn search_case_insensitive<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
-        let query = query.to_lowercase();
-        contents
-            .lines()
-            .filter(|x| x.to_lowercase().contains(&query))
-            .collect()
-    }
-}
-
-pub mod tpool {
-
-    use std::sync::mpsc;
-    use std::sync::Arc;
-    use std::sync::Mutex;
-    use std::thread;
-
-    enum Message {
-        NewJob(Job),
-        Terminate,
-    }
-
-    pub struct ThreadPool {
-        workers: Vec<Worker>,
-        sender: mpsc::Sender<Message>,
-    }
-
-    type Job = Box<dyn FnOnce() + Send + 'static>;
-
-    impl ThreadPool {
-        /// 创建线程池。
-        ///
-        /// 线程池中线程的数量。
-        ///
-        /// # Panics
-        ///
-        /// `new` 函数在 size 为 0 时会 panic。
-        pub fn new(size: usize) -> ThreadPool {
-            assert!(size > 0);
-
-            let (sender, receiver) = mpsc::channel();
-
-            let receiver = Arc::new(Mutex::new(receiver));
-
-            let mut workers = Vec::with_capacity(size);
-
-            for id in 0..size {
-                workers.push(Worker::new(id, Arc::clone(&receiver)));
-            }
-
-            ThreadPool { workers, sender }
-        }
-
-        pub fn execute<F>(&self, f: F)
-        where
-            F: FnOnce() + Send + 'static,
-        {
-            let job = Box::new(f);
-
-            self.sender.send(Message::NewJob(job)).unwrap();
-        }
-    }
-
-    impl Drop for ThreadPool {
-        fn drop(&mut self) {
-            println!("Sending terminate message to all workers.");
-
-            for _ in &mut self.workers {
-                self.sender.send(Message::Terminate).unwrap();
-            }
-
-            println!("Shutting down all workers.");
-
-            for worker in &mut self.workers {
-                println!("Shutting down worker {}", worker.id);
-
-                if let Some(thread) = worker.thread.take() {
-                    thread.join().unwrap();
-                }
-            }
-        }
-    }
-
-    struct Worker {
-        id: usize,
-        thread: Option<thread::JoinHandle<()>>,
-    }
-
-    impl Worker {
-        fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Message>>>) -> Worker {
-            let thread = thread::spawn(move || loop {
-                let message = receiver.lock().unwrap().recv().unwrap();
-
-                match message {
-                    Message::NewJob(job) => {
-                        println!("Worker {} got a job; executing.", id);
-
-                        job();
-                    }
-                    Message::Terminate => {
-                        println!("Worker {} was told to terminate.", id);
-
-                        break;
-                    }
-                }
-            });
-
-            Worker {
-                id,
-                thread: Some(thread),
-            }
-        }
-    }
-}
+pub mod tpool;
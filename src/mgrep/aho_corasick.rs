@@ -0,0 +1,179 @@
+//! 多模式字符串匹配：Aho-Corasick 自动机。
+//!
+//! 所有模式先建成一棵字典树，再用广度优先遍历补上失败链接。扫描文本时每个字符只走一次转移
+//! （失败时沿失败链接回退），所以不管有多少个模式，一行文本都只扫描一遍。
+//!
+//! 自动机按字符而不是按字节构建：忽略大小写时，模式和文本都逐个字符做简单大小写折叠，
+//! 折叠前后字符数不变，匹配的字符数就能换算回原文里的字节区间。
+//!
+//! 和 grep 一样返回最左最长的匹配：找到一个匹配以后继续扫描，直到不可能再出现更靠左的匹配。
+
+use std::collections::VecDeque;
+
+use super::unicode;
+
+const ROOT: usize = 0;
+
+#[derive(Debug, Clone)]
+struct State {
+    /// 按字符排序的转移
+    next: Vec<(char, usize)>,
+    fail: usize,
+    /// 从根到这个状态的字符数
+    depth: usize,
+    /// 以这个状态结尾的最长模式的字符数，包括沿失败链接能到达的较短模式
+    matched: Option<usize>,
+}
+
+impl State {
+    fn new(depth: usize) -> State {
+        State {
+            next: Vec::new(),
+            fail: ROOT,
+            depth,
+            matched: None,
+        }
+    }
+
+    fn get(&self, c: char) -> Option<usize> {
+        self.next
+            .binary_search_by_key(&c, |&(c, _)| c)
+            .ok()
+            .map(|i| self.next[i].1)
+    }
+}
+
+/// 同时查找多个字符串
+///
+/// ```
+/// use learning_rust::mgrep::aho_corasick::AhoCorasick;
+///
+/// let ac = AhoCorasick::new(["rust", "trust", "us"], false);
+/// assert_eq!(Some((3, 8)), ac.find_at("In Trust we rust", 0));
+/// assert_eq!(Some((12, 16)), ac.find_at("In Trust we rust", 8));
+/// ```
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    states: Vec<State>,
+    /// 根状态上 ASCII 字符的转移，大部分文本字符都是从根出发
+    root: [usize; 128],
+    case_sensitive: bool,
+}
+
+impl AhoCorasick {
+    pub fn new<I, P>(patterns: I, case_sensitive: bool) -> AhoCorasick
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<str>,
+    {
+        let fold = |c| fold(c, case_sensitive);
+        let mut states = vec![State::new(0)];
+
+        for pattern in patterns {
+            let mut s = ROOT;
+            for c in pattern.as_ref().chars().map(fold) {
+                s = match states[s].get(c) {
+                    Some(t) => t,
+                    None => {
+                        let t = states.len();
+                        states.push(State::new(states[s].depth + 1));
+                        let next = &mut states[s].next;
+                        let i = next.binary_search_by_key(&c, |&(c, _)| c).unwrap_err();
+                        next.insert(i, (c, t));
+                        t
+                    }
+                };
+            }
+            states[s].matched = Some(states[s].depth);
+        }
+
+        // 按深度从小到大补上失败链接，保证处理一个状态时它的失败状态已经处理完
+        let mut queue: VecDeque<usize> = states[ROOT].next.iter().map(|&(_, t)| t).collect();
+        for &t in &queue {
+            states[t].matched = states[t].matched.or(states[ROOT].matched);
+        }
+        while let Some(s) = queue.pop_front() {
+            for i in 0..states[s].next.len() {
+                let (c, t) = states[s].next[i];
+                let mut f = states[s].fail;
+                let fail = loop {
+                    if let Some(next) = states[f].get(c) {
+                        break next;
+                    }
+                    if f == ROOT {
+                        break ROOT;
+                    }
+                    f = states[f].fail;
+                };
+                states[t].fail = fail;
+                states[t].matched = states[t].matched.or(states[fail].matched);
+                queue.push_back(t);
+            }
+        }
+
+        let mut root = [ROOT; 128];
+        for &(c, t) in &states[ROOT].next {
+            if c.is_ascii() {
+                root[c as usize] = t;
+            }
+        }
+        AhoCorasick {
+            states,
+            root,
+            case_sensitive,
+        }
+    }
+
+    /// 从 `start` 开始查找最左最长的匹配，返回字节区间
+    pub fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        // (开始位置的字符序号, 结尾的字节偏移, 字符数)
+        let mut best = self.states[ROOT].matched.map(|_| (0, start, 0));
+        let mut s = ROOT;
+        let mut chars = 0;
+        for (i, c) in line[start..].char_indices() {
+            s = self.step(s, fold(c, self.case_sensitive));
+            chars += 1;
+            let state = &self.states[s];
+            // 之后的匹配至少从 `chars - depth` 开始，不会比已经找到的更靠左
+            if best.is_some_and(|(first, _, _)| chars - state.depth > first) {
+                break;
+            }
+            if let Some(len) = state.matched {
+                if best.is_none_or(|(first, _, _)| chars - len <= first) {
+                    best = Some((chars - len, start + i + c.len_utf8(), len));
+                }
+            }
+        }
+
+        let (_, end, len) = best?;
+        let begin = match len {
+            0 => end,
+            len => line[..end].char_indices().rev().nth(len - 1)?.0,
+        };
+        Some((begin, end))
+    }
+
+    fn step(&self, mut s: usize, c: char) -> usize {
+        loop {
+            if s == ROOT {
+                return if c.is_ascii() {
+                    self.root[c as usize]
+                } else {
+                    self.states[ROOT].get(c).unwrap_or(ROOT)
+                };
+            }
+            if let Some(t) = self.states[s].get(c) {
+                return t;
+            }
+            s = self.states[s].fail;
+        }
+    }
+}
+
+fn fold(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else {
+        unicode::simple_fold(c)
+    }
+}
@@ -0,0 +1,695 @@
+//! 命令行参数解析。
+//!
+//! 选项表 `OPTIONS` 同时用于解析和生成 `--help` 的用法说明，
+//! 新增选项时只需要在表里加一行，再在 `Config::apply` 里处理。
+
+use std::{
+    env,
+    error::Error,
+    fmt, fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use super::filter;
+
+/// `--color` 的取值
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// 要查找的模式，任意一个匹配就算匹配；来自第一个位置参数，或者 `-e` / `-f`
+    pub patterns: Vec<String>,
+    /// 要搜索的文件或目录，目录会被递归遍历；为空或者是 `-` 时读 stdin
+    pub paths: Vec<PathBuf>,
+    pub case_sensitive: bool,
+    /// `-E/--regex`：把模式当作正则表达式
+    pub regex: bool,
+    /// `-n`：输出行号
+    pub line_number: bool,
+    /// `-b`：输出每行在文件里的字节偏移
+    pub byte_offset: bool,
+    /// `--column`：输出第一个匹配所在的列（从 1 开始的字节列）
+    pub column: bool,
+    /// `--vimgrep`：每个匹配单独输出一行 `path:line:column:text`
+    pub vimgrep: bool,
+    /// `--json`：按 `json` 模块里的格式每行输出一个事件
+    pub json: bool,
+    /// `-c`：只输出每个文件匹配的行数
+    pub count: bool,
+    /// `-v`：选出不匹配的行
+    pub invert_match: bool,
+    /// `-l`：只输出有匹配的文件名
+    pub files_with_matches: bool,
+    /// `-L`：只输出没有匹配的文件名
+    pub files_without_match: bool,
+    /// `-m`：每个文件最多选出这么多行
+    pub max_count: Option<usize>,
+    /// `-q`：不输出任何内容，只通过退出码表示是否匹配
+    pub quiet: bool,
+    /// `-r`：输出时把每个匹配替换成这个模板，见 `replace` 模块
+    pub replace: Option<String>,
+    /// `--in-place`：把替换结果写回文件
+    pub in_place: bool,
+    /// `--dry-run`：不修改文件，输出替换前后的 unified diff
+    pub dry_run: bool,
+    /// `--fuzzy`：查找编辑距离不超过这个值的子串，结果按距离排序
+    pub fuzzy: Option<usize>,
+    /// `-w`：只匹配完整的单词
+    pub word: bool,
+    /// `-B`：每个匹配之前输出的上下文行数
+    pub before_context: usize,
+    /// `-A`：每个匹配之后输出的上下文行数
+    pub after_context: usize,
+    pub color: ColorChoice,
+    /// `-j`：同时搜索的文件数，0 表示按 CPU 核数
+    pub threads: usize,
+    /// `--include`：遍历目录时只搜索匹配这些 glob 的文件
+    pub include: Vec<String>,
+    /// `--exclude`：遍历目录时跳过匹配这些 glob 的文件和目录
+    pub exclude: Vec<String>,
+    /// `-t`：遍历目录时只搜索这些类型的文件，见 `filter::TYPES`
+    pub types: Vec<String>,
+    /// `--max-filesize`：遍历目录时跳过大于这么多字节的文件
+    pub max_filesize: Option<u64>,
+    /// `--hidden`：遍历目录时也搜索以 `.` 开头的文件和目录
+    pub hidden: bool,
+    /// `--follow`：遍历目录时跟随指向目录的符号链接
+    pub follow: bool,
+}
+
+/// 解析命令行参数时的错误
+///
+/// `Help` 和 `Version` 不算真正的错误，调用方应该把它们的 `Display` 输出到 stdout 并正常退出。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    MissingQuery,
+    UnknownOption(String),
+    MissingValue(String),
+    UnexpectedValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+    },
+    /// 读取 `-f` 指定的模式文件失败
+    PatternFile {
+        path: String,
+        message: String,
+    },
+    /// 选项必须和另一个选项一起使用
+    Requires {
+        option: String,
+        requires: String,
+    },
+    /// 两个选项不能同时使用
+    Conflicts(String, String),
+    /// `-t` 给出了 `filter::TYPES` 里没有的类型
+    UnknownType(String),
+    Help,
+    Version,
+}
+
+impl ConfigError {
+    /// 是否是 `--help` / `--version` 这类正常退出的请求
+    pub fn is_info(&self) -> bool {
+        matches!(self, ConfigError::Help | ConfigError::Version)
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::MissingQuery => write!(f, "Didn't get a query string"),
+            ConfigError::UnknownOption(opt) => write!(f, "unknown option '{}'", opt),
+            ConfigError::MissingValue(opt) => write!(f, "option '{}' requires a value", opt),
+            ConfigError::UnexpectedValue(opt) => {
+                write!(f, "option '{}' doesn't take a value", opt)
+            }
+            ConfigError::InvalidValue { option, value } => {
+                write!(f, "invalid value '{}' for option '{}'", value, option)
+            }
+            ConfigError::PatternFile { path, message } => {
+                write!(f, "can't read pattern file '{}': {}", path, message)
+            }
+            ConfigError::Requires { option, requires } => {
+                write!(f, "option '{}' requires '{}'", option, requires)
+            }
+            ConfigError::Conflicts(a, b) => {
+                write!(f, "options '{}' and '{}' can't be used together", a, b)
+            }
+            ConfigError::UnknownType(name) => {
+                let names: Vec<&str> = filter::TYPES.iter().map(|(name, _)| *name).collect();
+                write!(
+                    f,
+                    "unknown file type '{}', expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            }
+            ConfigError::Help => write!(f, "{}", usage()),
+            ConfigError::Version => write!(f, "minigrep {}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// 选项是否带值，里面是值的名字
+enum Value {
+    None,
+    Required(&'static str),
+    /// 只能用 `--name=value` 的形式给出
+    Optional(&'static str),
+}
+
+struct Opt {
+    short: Option<char>,
+    long: &'static str,
+    value: Value,
+    help: &'static str,
+}
+
+const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('e'),
+        long: "regexp",
+        value: Value::Required("PATTERN"),
+        help: "Search for PATTERN; may be given more than once",
+    },
+    Opt {
+        short: Some('f'),
+        long: "file",
+        value: Value::Required("FILE"),
+        help: "Read patterns from FILE, one per line (`-` for stdin)",
+    },
+    Opt {
+        short: Some('E'),
+        long: "regex",
+        value: Value::None,
+        help: "Treat QUERY as a regular expression",
+    },
+    Opt {
+        short: Some('i'),
+        long: "ignore-case",
+        value: Value::None,
+        help: "Ignore case distinctions (or set CASE_INSENSITIVE)",
+    },
+    Opt {
+        short: None,
+        long: "fuzzy",
+        value: Value::Required("N"),
+        help: "Match within edit distance N of QUERY, closest lines first",
+    },
+    Opt {
+        short: Some('w'),
+        long: "word-regexp",
+        value: Value::None,
+        help: "Only match whole words",
+    },
+    Opt {
+        short: Some('v'),
+        long: "invert-match",
+        value: Value::None,
+        help: "Select non-matching lines",
+    },
+    Opt {
+        short: Some('n'),
+        long: "line-number",
+        value: Value::None,
+        help: "Print line numbers",
+    },
+    Opt {
+        short: Some('b'),
+        long: "byte-offset",
+        value: Value::None,
+        help: "Print the byte offset of each line",
+    },
+    Opt {
+        short: None,
+        long: "column",
+        value: Value::None,
+        help: "Print the column of the first match",
+    },
+    Opt {
+        short: None,
+        long: "vimgrep",
+        value: Value::None,
+        help: "Print every match as PATH:LINE:COLUMN:TEXT",
+    },
+    Opt {
+        short: None,
+        long: "json",
+        value: Value::None,
+        help: "Print results as JSON Lines (see `mgrep::json`)",
+    },
+    Opt {
+        short: Some('A'),
+        long: "after-context",
+        value: Value::Required("NUM"),
+        help: "Print NUM lines of context after each match",
+    },
+    Opt {
+        short: Some('B'),
+        long: "before-context",
+        value: Value::Required("NUM"),
+        help: "Print NUM lines of context before each match",
+    },
+    Opt {
+        short: Some('C'),
+        long: "context",
+        value: Value::Required("NUM"),
+        help: "Print NUM lines of context around each match",
+    },
+    Opt {
+        short: Some('c'),
+        long: "count",
+        value: Value::None,
+        help: "Print only a count of matching lines per file",
+    },
+    Opt {
+        short: Some('l'),
+        long: "files-with-matches",
+        value: Value::None,
+        help: "Print only names of files with matches",
+    },
+    Opt {
+        short: Some('L'),
+        long: "files-without-match",
+        value: Value::None,
+        help: "Print only names of files without matches",
+    },
+    Opt {
+        short: Some('m'),
+        long: "max-count",
+        value: Value::Required("NUM"),
+        help: "Stop reading a file after NUM selected lines",
+    },
+    Opt {
+        short: Some('q'),
+        long: "quiet",
+        value: Value::None,
+        help: "Print nothing; exit 0 on the first match",
+    },
+    Opt {
+        short: Some('r'),
+        long: "replace",
+        value: Value::Required("TEXT"),
+        help: "Replace every match with TEXT ($1, ${name} refer to groups)",
+    },
+    Opt {
+        short: None,
+        long: "in-place",
+        value: Value::None,
+        help: "Write replacements back to the files",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        value: Value::None,
+        help: "Print replacements as a unified diff instead of writing",
+    },
+    Opt {
+        short: None,
+        long: "include",
+        value: Value::Required("GLOB"),
+        help: "Only search files matching GLOB when walking directories",
+    },
+    Opt {
+        short: None,
+        long: "exclude",
+        value: Value::Required("GLOB"),
+        help: "Skip files and directories matching GLOB",
+    },
+    Opt {
+        short: Some('t'),
+        long: "type",
+        value: Value::Required("TYPE"),
+        help: "Only search files of TYPE (rust, c, python, ...; see `filter::TYPES`)",
+    },
+    Opt {
+        short: None,
+        long: "max-filesize",
+        value: Value::Required("NUM[KMG]"),
+        help: "Skip files larger than NUM bytes (K, M, G suffixes allowed)",
+    },
+    Opt {
+        short: None,
+        long: "hidden",
+        value: Value::None,
+        help: "Search hidden files and directories",
+    },
+    Opt {
+        short: None,
+        long: "follow",
+        value: Value::None,
+        help: "Follow symbolic links to directories",
+    },
+    Opt {
+        short: None,
+        long: "color",
+        value: Value::Optional("WHEN"),
+        help: "Highlight output: auto, always or never",
+    },
+    Opt {
+        short: Some('j'),
+        long: "threads",
+        value: Value::Required("NUM"),
+        help: "Search NUM files in parallel (default: number of CPUs)",
+    },
+    Opt {
+        short: Some('h'),
+        long: "help",
+        value: Value::None,
+        help: "Print this help and exit",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        value: Value::None,
+        help: "Print version and exit",
+    },
+];
+
+/// 根据 `OPTIONS` 生成的用法说明
+pub fn usage() -> String {
+    let mut lines = vec![
+        String::from("Usage: minigrep [OPTIONS] QUERY [PATH]..."),
+        String::from("       minigrep [OPTIONS] -e PATTERN... [PATH]..."),
+        String::new(),
+        String::from("Options:"),
+    ];
+    let specs: Vec<(String, &str)> = OPTIONS
+        .iter()
+        .map(|opt| {
+            let short = match opt.short {
+                Some(c) => format!("-{}, ", c),
+                None => String::from("    "),
+            };
+            let value = match opt.value {
+                Value::None => String::new(),
+                Value::Required(name) => format!(" <{}>", name),
+                Value::Optional(name) => format!("[={}]", name),
+            };
+            (format!("{}--{}{}", short, opt.long, value), opt.help)
+        })
+        .collect();
+    let width = specs.iter().map(|(spec, _)| spec.len()).max().unwrap_or(0);
+    for (spec, help) in specs {
+        lines.push(format!("  {:width$}  {}", spec, help, width = width));
+    }
+    lines.push(String::new());
+    lines.push(String::from("Use `--` to stop option parsing."));
+    lines.join("\n")
+}
+
+/// 解析过程中需要等所有参数读完才能决定的值
+#[derive(Default)]
+struct Pending {
+    /// 是否通过 `-e` / `-f` 给出了模式，这时所有位置参数都是路径
+    patterns: bool,
+    ignore_case: bool,
+    context: Option<usize>,
+    before: Option<usize>,
+    after: Option<usize>,
+}
+
+fn number(opt: &Opt, value: Option<String>) -> Result<usize, ConfigError> {
+    let value = value.unwrap_or_default();
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        option: format!("--{}", opt.long),
+        value,
+    })
+}
+
+/// 字节数，可以带 `K` / `M` / `G` 后缀（1024 进制）
+fn size(opt: &Opt, value: Option<String>) -> Result<u64, ConfigError> {
+    let value = value.unwrap_or_default();
+    let (digits, shift) = match value.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&value[..i], 10),
+        Some((i, 'M' | 'm')) => (&value[..i], 20),
+        Some((i, 'G' | 'g')) => (&value[..i], 30),
+        _ => (&value[..], 0),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(1 << shift))
+        .ok_or_else(|| ConfigError::InvalidValue {
+            option: format!("--{}", opt.long),
+            value: value.clone(),
+        })
+}
+
+/// 读取模式文件，`-` 表示 stdin
+fn read_patterns(path: &str) -> io::Result<String> {
+    if path == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        Ok(contents)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+impl Config {
+    /// 解析命令行参数，第一个参数是程序名
+    pub fn new<I>(args: I) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut config = Config {
+            patterns: Vec::new(),
+            paths: Vec::new(),
+            case_sensitive: true,
+            regex: false,
+            line_number: false,
+            byte_offset: false,
+            column: false,
+            vimgrep: false,
+            json: false,
+            count: false,
+            invert_match: false,
+            files_with_matches: false,
+            files_without_match: false,
+            max_count: None,
+            quiet: false,
+            replace: None,
+            in_place: false,
+            dry_run: false,
+            fuzzy: None,
+            word: false,
+            before_context: 0,
+            after_context: 0,
+            color: ColorChoice::Auto,
+            threads: 0,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            types: Vec::new(),
+            max_filesize: None,
+            hidden: false,
+            follow: false,
+        };
+        let mut pending = Pending::default();
+        let mut positional = Vec::new();
+
+        let mut args = args.into_iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                positional.extend(args.by_ref());
+                break;
+            }
+            if let Some(long) = arg.strip_prefix("--") {
+                let (name, mut value) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                let opt = OPTIONS
+                    .iter()
+                    .find(|opt| opt.long == name)
+                    .ok_or_else(|| ConfigError::UnknownOption(arg.clone()))?;
+                match opt.value {
+                    Value::None if value.is_some() => {
+                        return Err(ConfigError::UnexpectedValue(arg.clone()))
+                    }
+                    Value::Required(_) if value.is_none() => {
+                        value = Some(
+                            args.next()
+                                .ok_or_else(|| ConfigError::MissingValue(arg.clone()))?,
+                        );
+                    }
+                    _ => {}
+                }
+                config.apply(opt, value, &mut pending)?;
+            } else if arg.len() > 1 && arg.starts_with('-') {
+                // 允许合并短选项，例如 `-inv`、`-nA3`
+                for (i, c) in arg.char_indices().skip(1) {
+                    let opt = OPTIONS
+                        .iter()
+                        .find(|opt| opt.short == Some(c))
+                        .ok_or_else(|| ConfigError::UnknownOption(format!("-{}", c)))?;
+                    if let Value::Required(_) = opt.value {
+                        let rest = &arg[i + c.len_utf8()..];
+                        let value = if rest.is_empty() {
+                            args.next()
+                                .ok_or_else(|| ConfigError::MissingValue(format!("-{}", c)))?
+                        } else {
+                            rest.to_string()
+                        };
+                        config.apply(opt, Some(value), &mut pending)?;
+                        break;
+                    }
+                    config.apply(opt, None, &mut pending)?;
+                }
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        if !pending.patterns {
+            let query = positional.next().ok_or(ConfigError::MissingQuery)?;
+            config.patterns.push(query);
+        }
+        // 没有路径时读 stdin
+        config.paths = positional.map(PathBuf::from).collect();
+
+        // 没有 `-i` 时再看环境变量
+        config.case_sensitive = !pending.ignore_case && env::var("CASE_INSENSITIVE").is_err();
+        // 和 grep 一样，`-A` / `-B` 优先于 `-C`
+        if let Some(context) = pending.context {
+            config.before_context = pending.before.unwrap_or(context);
+            config.after_context = pending.after.unwrap_or(context);
+        } else {
+            config.before_context = pending.before.unwrap_or(0);
+            config.after_context = pending.after.unwrap_or(0);
+        }
+
+        if config.replace.is_none() {
+            for (set, option) in [
+                (config.in_place, "--in-place"),
+                (config.dry_run, "--dry-run"),
+            ] {
+                if set {
+                    return Err(ConfigError::Requires {
+                        option: String::from(option),
+                        requires: String::from("--replace"),
+                    });
+                }
+            }
+        }
+
+        if config.fuzzy.is_some() {
+            let conflicts = [
+                (config.regex, "--regex"),
+                (config.word, "--word-regexp"),
+                (config.patterns.len() > 1, "--regexp"),
+            ];
+            if let Some((_, option)) = conflicts.iter().find(|(set, _)| *set) {
+                return Err(ConfigError::Conflicts(
+                    String::from("--fuzzy"),
+                    option.to_string(),
+                ));
+            }
+        }
+        if config.json {
+            let conflicts = [
+                (config.vimgrep, "--vimgrep"),
+                (config.count, "--count"),
+                (config.files_with_matches, "--files-with-matches"),
+                (config.files_without_match, "--files-without-match"),
+                (config.in_place, "--in-place"),
+                (config.dry_run, "--dry-run"),
+            ];
+            if let Some((_, option)) = conflicts.iter().find(|(set, _)| *set) {
+                return Err(ConfigError::Conflicts(
+                    String::from("--json"),
+                    option.to_string(),
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn apply(
+        &mut self,
+        opt: &Opt,
+        value: Option<String>,
+        pending: &mut Pending,
+    ) -> Result<(), ConfigError> {
+        match opt.long {
+            // 和 grep 一样，一个值里的多行是多个模式
+            "regexp" => {
+                let value = value.unwrap_or_default();
+                self.patterns.extend(value.split('\n').map(String::from));
+                pending.patterns = true;
+            }
+            "file" => {
+                let path = value.unwrap_or_default();
+                let contents = read_patterns(&path).map_err(|e| ConfigError::PatternFile {
+                    path: path.clone(),
+                    message: e.to_string(),
+                })?;
+                let lines = contents.lines().map(|line| line.trim_end_matches('\r'));
+                self.patterns.extend(lines.map(String::from));
+                pending.patterns = true;
+            }
+            "regex" => self.regex = true,
+            "fuzzy" => self.fuzzy = Some(number(opt, value)?),
+            "ignore-case" => pending.ignore_case = true,
+            "word-regexp" => self.word = true,
+            "invert-match" => self.invert_match = true,
+            "line-number" => self.line_number = true,
+            "byte-offset" => self.byte_offset = true,
+            "column" => self.column = true,
+            "vimgrep" => self.vimgrep = true,
+            "json" => self.json = true,
+            "after-context" => pending.after = Some(number(opt, value)?),
+            "before-context" => pending.before = Some(number(opt, value)?),
+            "context" => pending.context = Some(number(opt, value)?),
+            "count" => self.count = true,
+            "files-with-matches" => self.files_with_matches = true,
+            "files-without-match" => self.files_without_match = true,
+            "max-count" => self.max_count = Some(number(opt, value)?),
+            "quiet" => self.quiet = true,
+            "replace" => self.replace = value,
+            "in-place" => self.in_place = true,
+            "dry-run" => self.dry_run = true,
+            "include" => self.include.push(value.unwrap_or_default()),
+            "exclude" => self.exclude.push(value.unwrap_or_default()),
+            "type" => {
+                let name = value.unwrap_or_default();
+                if filter::type_globs(&name).is_none() {
+                    return Err(ConfigError::UnknownType(name));
+                }
+                self.types.push(name);
+            }
+            "max-filesize" => self.max_filesize = Some(size(opt, value)?),
+            "hidden" => self.hidden = true,
+            "follow" => self.follow = true,
+            "color" => {
+                self.color = match value.as_deref() {
+                    None | Some("always") => ColorChoice::Always,
+                    Some("auto") => ColorChoice::Auto,
+                    Some("never") => ColorChoice::Never,
+                    Some(other) => {
+                        return Err(ConfigError::InvalidValue {
+                            option: String::from("--color"),
+                            value: other.to_string(),
+                        })
+                    }
+                }
+            }
+            "threads" => self.threads = number(opt, value)?,
+            "help" => return Err(ConfigError::Help),
+            "version" => return Err(ConfigError::Version),
+            _ => unreachable!("option --{} is not handled", opt.long),
+        }
+        Ok(())
+    }
+}
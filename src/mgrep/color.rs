@@ -0,0 +1,210 @@
+//! 输出高亮：决定是否上色，以及解析 `GREP_COLORS` 风格的配色。
+//!
+//! 配色字符串形如 `ms=01;31:fn=35:ln=32:se=36`，每一项的值可以是 SGR 代码，
+//! 也可以像 `utils::color_pringln` 那样直接写颜色名，例如 `fn=magenta`。
+//! 支持的键：
+//! - `ms` 匹配到的文本
+//! - `fn` 文件名
+//! - `ln` 行号和列号
+//! - `bn` 字节偏移
+//! - `se` 分隔符 `:`、`-` 和 `--`
+
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+use colored::{Color, ColoredString, Colorize};
+
+use super::ColorChoice;
+
+/// 一种文字样式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dimmed: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reversed: bool,
+}
+
+impl Style {
+    pub fn fg(color: Color) -> Style {
+        Style {
+            fg: Some(color),
+            ..Style::default()
+        }
+    }
+
+    /// 解析一项配色的值，无法识别时返回 `None`
+    pub fn parse(value: &str) -> Option<Style> {
+        if value.is_empty() {
+            return Some(Style::default());
+        }
+        if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+            return value.parse().ok().map(Style::fg);
+        }
+
+        let mut style = Style::default();
+        let mut codes = value.split(';').map(|code| code.parse::<u8>().ok());
+        while let Some(code) = codes.next() {
+            match code? {
+                0 => style = Style::default(),
+                1 => style.bold = true,
+                2 => style.dimmed = true,
+                3 => style.italic = true,
+                4 => style.underline = true,
+                7 => style.reversed = true,
+                n @ 30..=37 => style.fg = Some(basic_color(n - 30, false)),
+                n @ 90..=97 => style.fg = Some(basic_color(n - 90, true)),
+                n @ 40..=47 => style.bg = Some(basic_color(n - 40, false)),
+                n @ 100..=107 => style.bg = Some(basic_color(n - 100, true)),
+                39 => style.fg = None,
+                49 => style.bg = None,
+                // 38;2;r;g;b 和 48;2;r;g;b 真彩色
+                n @ (38 | 48) => {
+                    if codes.next()?? != 2 {
+                        return None;
+                    }
+                    let (r, g, b) = (codes.next()??, codes.next()??, codes.next()??);
+                    let color = Some(Color::TrueColor { r, g, b });
+                    if n == 38 {
+                        style.fg = color;
+                    } else {
+                        style.bg = color;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(style)
+    }
+
+    pub fn paint(&self, text: &str) -> ColoredString {
+        let mut s = text.normal();
+        if let Some(fg) = self.fg {
+            s = s.color(fg);
+        }
+        if let Some(bg) = self.bg {
+            s = s.on_color(bg);
+        }
+        if self.bold {
+            s = s.bold();
+        }
+        if self.dimmed {
+            s = s.dimmed();
+        }
+        if self.italic {
+            s = s.italic();
+        }
+        if self.underline {
+            s = s.underline();
+        }
+        if self.reversed {
+            s = s.reversed();
+        }
+        s
+    }
+}
+
+fn basic_color(n: u8, bright: bool) -> Color {
+    let colors = if bright {
+        [
+            Color::BrightBlack,
+            Color::BrightRed,
+            Color::BrightGreen,
+            Color::BrightYellow,
+            Color::BrightBlue,
+            Color::BrightMagenta,
+            Color::BrightCyan,
+            Color::BrightWhite,
+        ]
+    } else {
+        [
+            Color::Black,
+            Color::Red,
+            Color::Green,
+            Color::Yellow,
+            Color::Blue,
+            Color::Magenta,
+            Color::Cyan,
+            Color::White,
+        ]
+    };
+    colors[n as usize]
+}
+
+/// 整套配色
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    pub matched: Style,
+    pub filename: Style,
+    pub line_number: Style,
+    pub byte_offset: Style,
+    pub separator: Style,
+}
+
+impl Default for Colors {
+    /// 和 GNU grep 的默认配色一致
+    fn default() -> Colors {
+        Colors {
+            matched: Style {
+                bold: true,
+                ..Style::fg(Color::Red)
+            },
+            filename: Style::fg(Color::Magenta),
+            line_number: Style::fg(Color::Green),
+            byte_offset: Style::fg(Color::Green),
+            separator: Style::fg(Color::Cyan),
+        }
+    }
+}
+
+impl Colors {
+    /// 在默认配色上应用 `spec`，无法识别的项直接忽略
+    pub fn parse(spec: &str) -> Colors {
+        let mut colors = Colors::default();
+        for item in spec.split(':') {
+            let (key, value) = match item.split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let style = match Style::parse(value) {
+                Some(style) => style,
+                None => continue,
+            };
+            match key {
+                "ms" | "mt" => colors.matched = style,
+                "fn" => colors.filename = style,
+                "ln" => colors.line_number = style,
+                "bn" => colors.byte_offset = style,
+                "se" => colors.separator = style,
+                _ => {}
+            }
+        }
+        colors
+    }
+
+    /// 读取环境变量 `GREP_COLORS`
+    pub fn from_env() -> Colors {
+        match env::var("GREP_COLORS") {
+            Ok(spec) => Colors::parse(&spec),
+            Err(_) => Colors::default(),
+        }
+    }
+}
+
+/// 根据 `--color` 决定是否上色；`auto` 时要求 stdout 是终端，并且没有设置 `NO_COLOR`、`TERM=dumb`
+pub fn enabled(choice: ColorChoice) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            io::stdout().is_terminal()
+                && env::var_os("NO_COLOR").is_none()
+                && env::var("TERM").map_or(true, |term| term != "dumb")
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! `--in-place` 和 `--dry-run`：把 `--replace` 的结果写回文件，或者输出 unified diff。
+//!
+//! 写回时先写到同一目录下的临时文件，再用 `rename` 覆盖原文件。`rename` 在同一个文件系统里
+//! 是原子操作，所以其他进程要么看到旧文件，要么看到完整的新文件，中途出错也不会留下写了一半的文件。
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    process,
+};
+
+use super::{matcher::Matcher, replace::Replacer, searcher::Stats, walk, Config, STDIN};
+
+/// diff 里每处修改前后保留的行数
+const DIFF_CONTEXT: usize = 3;
+
+/// 替换 `path` 里的匹配；`--dry-run` 时把 diff 写到 `out`，否则写回文件
+///
+/// 返回的 `Stats::selected` 是被修改的行数。
+pub fn edit_file<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    replacer: &Replacer,
+    path: &Path,
+    out: &mut W,
+) -> io::Result<Stats> {
+    if path == Path::new(STDIN) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't edit standard input in place",
+        ));
+    }
+    let mut stats = Stats::default();
+    let bytes = fs::read(path)?;
+    if walk::is_binary(&bytes) {
+        stats.binary = true;
+        return Ok(stats);
+    }
+    let contents = String::from_utf8(bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.utf8_error()))?;
+
+    let max_count = config.max_count.unwrap_or(usize::MAX);
+    let old: Vec<&str> = contents.split_inclusive('\n').collect();
+    let mut new = Vec::with_capacity(old.len());
+    for raw in &old {
+        stats.lines += 1;
+        let (line, ending) = split_ending(raw);
+        let replaced = match replacer.replace(matcher, line) {
+            Some((replaced, _)) if stats.selected < max_count && !config.invert_match => replaced,
+            _ => {
+                new.push(raw.to_string());
+                continue;
+            }
+        };
+        stats.selected += 1;
+        new.push(replaced + ending);
+    }
+    if stats.selected == 0 {
+        return Ok(stats);
+    }
+
+    if config.dry_run {
+        write_diff(out, &path.display().to_string(), &old, &new)?;
+    } else {
+        write_atomically(path, new.concat().as_bytes())?;
+    }
+    Ok(stats)
+}
+
+/// 把一行分成内容和换行符（`\n`、`\r\n` 或者文件末尾的空串）
+fn split_ending(raw: &str) -> (&str, &str) {
+    let line = raw.strip_suffix('\n').unwrap_or(raw);
+    let line = if line.len() < raw.len() {
+        line.strip_suffix('\r').unwrap_or(line)
+    } else {
+        line
+    };
+    raw.split_at(line.len())
+}
+
+/// 通过临时文件和 `rename` 原子地覆盖 `path`，保留原来的权限；符号链接会修改它指向的文件
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let (dir, name) = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => (dir, name.to_string_lossy()),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file")),
+    };
+    let tmp = dir.join(format!(".{}.minigrep-{}.tmp", name, process::id()));
+
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents)?;
+        file.set_permissions(fs::metadata(&path)?.permissions())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// 输出 `old` 到 `new` 的 unified diff
+///
+/// `old` 是原文件的每一行（包括换行符），`new[i]` 是第 `i` 行替换后的文本，
+/// 替换文本里有换行符时可能对应多行。
+fn write_diff<W: Write>(out: &mut W, path: &str, old: &[&str], new: &[String]) -> io::Result<()> {
+    let changed: Vec<usize> = (0..old.len()).filter(|&i| old[i] != new[i]).collect();
+    // `new_start[i]` 是原文件第 `i` 行在新文件里对应的行号（从 0 开始）
+    let mut new_start = Vec::with_capacity(old.len() + 1);
+    let mut n = 0;
+    for text in new {
+        new_start.push(n);
+        n += text.split_inclusive('\n').count();
+    }
+    new_start.push(n);
+
+    writeln!(out, "--- a/{}", path)?;
+    writeln!(out, "+++ b/{}", path)?;
+    let mut i = 0;
+    while i < changed.len() {
+        // 相邻两处修改之间的行数不超过两倍上下文时合并成一个 hunk
+        let mut j = i;
+        while j + 1 < changed.len() && changed[j + 1] - changed[j] <= 2 * DIFF_CONTEXT + 1 {
+            j += 1;
+        }
+        let start = changed[i].saturating_sub(DIFF_CONTEXT);
+        let end = (changed[j] + DIFF_CONTEXT + 1).min(old.len());
+        let new_len = new_start[end] - new_start[start];
+        writeln!(
+            out,
+            "@@ -{} +{} @@",
+            range(start, end - start),
+            range(new_start[start], new_len)
+        )?;
+
+        let mut k = start;
+        while k < end {
+            if old[k] == new[k] {
+                diff_line(out, ' ', old[k])?;
+                k += 1;
+                continue;
+            }
+            // 连续修改的行先全部输出 `-`，再全部输出 `+`
+            let run_end = (k..end).find(|&m| old[m] == new[m]).unwrap_or(end);
+            for line in &old[k..run_end] {
+                diff_line(out, '-', line)?;
+            }
+            for text in &new[k..run_end] {
+                for line in text.split_inclusive('\n') {
+                    diff_line(out, '+', line)?;
+                }
+            }
+            k = run_end;
+        }
+        i = j + 1;
+    }
+    Ok(())
+}
+
+/// hunk 头里的 `start,len`，行号从 1 开始；空范围的起点是它前面一行
+fn range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start)
+    } else {
+        format!("{},{}", start + 1, len)
+    }
+}
+
+fn diff_line<W: Write>(out: &mut W, prefix: char, line: &str) -> io::Result<()> {
+    write!(out, "{}{}", prefix, line)?;
+    if !line.ends_with('\n') {
+        writeln!(out, "\n\\ No newline at end of file")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! 遍历目录时决定哪些文件需要搜索。
+//!
+//! 在 `.gitignore` 之后生效，只作用于遍历时遇到的条目，命令行上直接给出的路径总是会被搜索：
+//!
+//! - `--include` / `-t`：只搜索文件名匹配的文件；两者都给出时要同时满足，同一种可以给出多个
+//! - `--exclude`：跳过匹配的文件和目录
+//! - `--max-filesize`：跳过超过这个大小的文件
+//! - 默认不跳过隐藏文件（以 `.` 开头），`minigrep` 没有 `--hidden` 时跳过
+//! - 默认不跟随指向目录的符号链接，跟随时遇到指回上层目录的链接会报错
+//!
+//! glob 里有 `/` 时相对遍历的起点匹配，否则只匹配文件名，和忽略文件的规则一样。
+
+use std::fs::Metadata;
+
+use super::{glob::Glob, Config};
+
+/// `-t` 能用的文件类型和对应的 glob，按名字排序
+pub const TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.h"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs", "*.jsx"]),
+    ("json", &["*.json"]),
+    ("log", &["*.log", "*.log.gz"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("python", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// 文件类型对应的 glob
+pub fn type_globs(name: &str) -> Option<&'static [&'static str]> {
+    TYPES
+        .iter()
+        .find(|(type_name, _)| *type_name == name)
+        .map(|(_, globs)| *globs)
+}
+
+/// 一组 glob，有 `/` 的匹配相对路径，否则匹配文件名
+#[derive(Debug, Clone, Default)]
+struct Globs(Vec<(Glob, bool)>);
+
+impl Globs {
+    fn add(&mut self, pattern: &str) {
+        let anchored = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        self.0.push((Glob::new(pattern), anchored));
+    }
+
+    fn is_match(&self, relative: &str, name: &str) -> bool {
+        self.0.iter().any(|(glob, anchored)| {
+            if *anchored {
+                glob.is_match(relative)
+            } else {
+                glob.is_match(name)
+            }
+        })
+    }
+}
+
+/// 文件过滤规则
+///
+/// ```
+/// use learning_rust::mgrep::filter::Filter;
+///
+/// let filter = Filter::new().file_type("rust").exclude("tests/**");
+/// assert!(filter.is_match("src/lib.rs", false));
+/// assert!(!filter.is_match("src/lib.c", false));
+/// assert!(!filter.is_match("tests/a.rs", false));
+/// // 目录不受 `-t` 影响
+/// assert!(filter.is_match("src", true));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    include: Globs,
+    exclude: Globs,
+    /// `None` 表示没有限制文件类型；给出了不存在的类型时是空的，什么都不匹配
+    types: Option<Globs>,
+    max_filesize: Option<u64>,
+    skip_hidden: bool,
+    follow_links: bool,
+}
+
+impl Filter {
+    /// 不过滤任何文件
+    pub fn new() -> Filter {
+        Filter::default()
+    }
+
+    /// 按 `minigrep` 的命令行参数设置
+    pub fn from_config(config: &Config) -> Filter {
+        let mut filter = Filter::new()
+            .max_filesize(config.max_filesize)
+            .skip_hidden(!config.hidden)
+            .follow_links(config.follow);
+        for glob in &config.include {
+            filter = filter.include(glob);
+        }
+        for glob in &config.exclude {
+            filter = filter.exclude(glob);
+        }
+        for name in &config.types {
+            filter = filter.file_type(name);
+        }
+        filter
+    }
+
+    /// 只搜索匹配 `glob` 的文件
+    pub fn include(mut self, glob: &str) -> Filter {
+        self.include.add(glob);
+        self
+    }
+
+    /// 跳过匹配 `glob` 的文件和目录
+    pub fn exclude(mut self, glob: &str) -> Filter {
+        self.exclude.add(glob);
+        self
+    }
+
+    /// 只搜索 `TYPES` 里这种类型的文件
+    pub fn file_type(mut self, name: &str) -> Filter {
+        let types = self.types.get_or_insert_with(Globs::default);
+        for glob in type_globs(name).unwrap_or_default() {
+            types.add(glob);
+        }
+        self
+    }
+
+    /// 跳过大于 `max` 字节的文件
+    pub fn max_filesize(mut self, max: Option<u64>) -> Filter {
+        self.max_filesize = max;
+        self
+    }
+
+    /// 跳过以 `.` 开头的文件和目录
+    pub fn skip_hidden(mut self, yes: bool) -> Filter {
+        self.skip_hidden = yes;
+        self
+    }
+
+    /// 跟随指向目录的符号链接
+    pub fn follow_links(mut self, yes: bool) -> Filter {
+        self.follow_links = yes;
+        self
+    }
+
+    pub fn is_following_links(&self) -> bool {
+        self.follow_links
+    }
+
+    /// `relative` 是相对遍历起点的路径，用 `/` 分隔
+    pub fn is_match(&self, relative: &str, is_dir: bool) -> bool {
+        let name = relative.rsplit('/').next().unwrap_or(relative);
+        if self.skip_hidden && name.starts_with('.') {
+            return false;
+        }
+        if self.exclude.is_match(relative, name) {
+            return false;
+        }
+        if is_dir {
+            return true;
+        }
+        if !self.include.0.is_empty() && !self.include.is_match(relative, name) {
+            return false;
+        }
+        self.types
+            .as_ref()
+            .is_none_or(|types| types.is_match(relative, name))
+    }
+
+    /// 除了路径，再检查文件大小
+    pub fn is_match_metadata(&self, relative: &str, metadata: &Metadata) -> bool {
+        if !self.is_match(relative, metadata.is_dir()) {
+            return false;
+        }
+        metadata.is_dir() || self.max_filesize.is_none_or(|max| metadata.len() <= max)
+    }
+}
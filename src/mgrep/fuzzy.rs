@@ -0,0 +1,202 @@
+//! `--fuzzy=N`：查找和 query 的 Levenshtein 编辑距离不超过 N 的子串。
+//!
+//! query 不超过 64 个字符时用 Myers 的位并行算法：DP 矩阵的一整列用两个 64 位整数表示
+//! （每个格子和上一格相比是 +1、-1 还是不变），每读一个字符只需要十几次位运算。
+//! 更长的 query 退回普通的逐格 DP。两种方式算的都是“以当前位置结尾的子串和 query
+//! 的最小编辑距离”，所以 query 可以出现在行内任意位置。
+//!
+//! 找到结尾以后，再从结尾往回对 query 做一次小的 DP 确定开头，用来高亮匹配的文本。
+//! 距离按字符计算，忽略大小写时比较的是简单大小写折叠后的字符。
+
+use super::unicode;
+
+/// query 的每个字符在哪些位置出现
+#[derive(Debug, Clone)]
+struct Peq {
+    ascii: [u64; 128],
+    other: Vec<(char, u64)>,
+}
+
+impl Peq {
+    fn new(pattern: &[char]) -> Peq {
+        let mut peq = Peq {
+            ascii: [0; 128],
+            other: Vec::new(),
+        };
+        for (i, &c) in pattern.iter().enumerate() {
+            let bit = 1 << i;
+            if c.is_ascii() {
+                peq.ascii[c as usize] |= bit;
+            } else {
+                match peq.other.iter_mut().find(|(o, _)| *o == c) {
+                    Some((_, mask)) => *mask |= bit,
+                    None => peq.other.push((c, bit)),
+                }
+            }
+        }
+        peq
+    }
+
+    fn get(&self, c: char) -> u64 {
+        if c.is_ascii() {
+            self.ascii[c as usize]
+        } else {
+            self.other
+                .iter()
+                .find(|&&(o, _)| o == c)
+                .map_or(0, |&(_, mask)| mask)
+        }
+    }
+}
+
+/// 近似匹配一个字符串
+///
+/// ```
+/// use learning_rust::mgrep::fuzzy::Fuzzy;
+///
+/// let fuzzy = Fuzzy::new("connection", 2, true);
+/// assert_eq!(Some(1), fuzzy.distance("error: conection refused"));
+/// assert_eq!(Some((7, 16)), fuzzy.find_at("error: conection refused", 0));
+/// assert_eq!(None, fuzzy.distance("error: timeout"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    pattern: Vec<char>,
+    max_distance: usize,
+    case_sensitive: bool,
+    /// query 不超过 64 个字符时才有
+    peq: Option<Peq>,
+}
+
+impl Fuzzy {
+    pub fn new(query: &str, max_distance: usize, case_sensitive: bool) -> Fuzzy {
+        let pattern: Vec<char> = query.chars().map(|c| fold(c, case_sensitive)).collect();
+        let peq = (pattern.len() <= 64).then(|| Peq::new(&pattern));
+        Fuzzy {
+            pattern,
+            max_distance,
+            case_sensitive,
+            peq,
+        }
+    }
+
+    pub fn max_distance(&self) -> usize {
+        self.max_distance
+    }
+
+    /// 行内和 query 最接近的子串的编辑距离，超过上限时返回 `None`
+    pub fn distance(&self, line: &str) -> Option<usize> {
+        let mut best = self.pattern.len();
+        self.scan(line, 0, |_, distance| {
+            best = best.min(distance);
+            best > 0
+        });
+        (best <= self.max_distance).then_some(best)
+    }
+
+    /// 从 `start` 开始找第一个足够接近的子串
+    ///
+    /// 找到第一个距离不超过上限的结尾后，继续往后读，直到距离不再变小。
+    pub fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        if self.pattern.len() <= self.max_distance {
+            return Some((start, start));
+        }
+        let mut found: Option<(usize, usize)> = None;
+        self.scan(line, start, |end, distance| match found {
+            None => {
+                if distance <= self.max_distance {
+                    found = Some((end, distance));
+                }
+                true
+            }
+            Some((_, best)) if distance < best => {
+                found = Some((end, distance));
+                true
+            }
+            Some(_) => false,
+        });
+        let (end, distance) = found?;
+        Some((self.start_of(line, start, end, distance), end))
+    }
+
+    /// 依次读入 `line[start..]` 的字符，每读一个就把结尾的字节偏移和当前的最小距离交给 `f`，
+    /// `f` 返回 `false` 时停止
+    fn scan(&self, line: &str, start: usize, mut f: impl FnMut(usize, usize) -> bool) {
+        let m = self.pattern.len();
+        let chars = line[start..]
+            .char_indices()
+            .map(|(i, c)| (start + i + c.len_utf8(), fold(c, self.case_sensitive)));
+
+        if let Some(peq) = &self.peq {
+            // Myers (1999)，按 Hyyrö 的写法；`pv` / `mv` 是当前列相邻两格的 +1 / -1
+            let last = 1u64 << (m.max(1) - 1);
+            let (mut pv, mut mv, mut score) = (!0u64, 0u64, m);
+            for (end, c) in chars {
+                let eq = peq.get(c);
+                let xv = eq | mv;
+                let xh = (((eq & pv).wrapping_add(pv)) ^ pv) | eq;
+                let ph = mv | !(xh | pv);
+                let mh = pv & xh;
+                if ph & last != 0 {
+                    score += 1;
+                } else if mh & last != 0 {
+                    score -= 1;
+                }
+                // 子串可以从任意位置开始，所以第 0 行始终是 0，移位时不补 1
+                let (ph, mh) = (ph << 1, mh << 1);
+                pv = mh | !(xv | ph);
+                mv = ph & xv;
+                if !f(end, score) {
+                    return;
+                }
+            }
+            return;
+        }
+
+        let mut column: Vec<usize> = (0..=m).collect();
+        for (end, c) in chars {
+            let mut diagonal = column[0];
+            for i in 1..=m {
+                let cost = if self.pattern[i - 1] == c { 0 } else { 1 };
+                let next = (diagonal + cost).min(column[i] + 1).min(column[i - 1] + 1);
+                diagonal = column[i];
+                column[i] = next;
+            }
+            if !f(end, column[m]) {
+                return;
+            }
+        }
+    }
+
+    /// 从 `end` 往回找距离为 `distance` 的匹配的开头，有多个时取最短的
+    fn start_of(&self, line: &str, start: usize, end: usize, distance: usize) -> usize {
+        let m = self.pattern.len();
+        // 反过来的 query 和 `end` 之前的 `j` 个字符的编辑距离
+        let mut column: Vec<usize> = (0..=m).collect();
+        let mut begin = end;
+        for (j, (i, c)) in line[start..end].char_indices().rev().enumerate() {
+            if column[m] <= distance || j > m + distance {
+                break;
+            }
+            let c = fold(c, self.case_sensitive);
+            let mut diagonal = column[0];
+            column[0] = j + 1;
+            for k in 1..=m {
+                let cost = if self.pattern[m - k] == c { 0 } else { 1 };
+                let next = (diagonal + cost).min(column[k] + 1).min(column[k - 1] + 1);
+                diagonal = column[k];
+                column[k] = next;
+            }
+            begin = start + i;
+        }
+        begin
+    }
+}
+
+fn fold(c: char, case_sensitive: bool) -> char {
+    if case_sensitive {
+        c
+    } else {
+        unicode::simple_fold(c)
+    }
+}
@@ -0,0 +1,141 @@
+//! `.gitignore` 风格的 glob 匹配。
+//!
+//! - `*` 匹配除 `/` 以外的任意字符
+//! - `?` 匹配除 `/` 以外的单个字符
+//! - `[abc]`、`[a-z]`、`[!a-z]` 字符类
+//! - `**/` 匹配零个或多个目录，`/**` 匹配目录下的一切
+//! - `\` 转义下一个字符
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    /// `**/`，匹配空串或者以 `/` 结尾的任意串
+    AnyDirs,
+    /// 结尾或中间单独出现的 `**`，匹配任意串
+    AnyPath,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    pattern: String,
+    tokens: Vec<Token>,
+}
+
+impl Glob {
+    /// 解析 glob；不完整的 `[` 按字面量处理，所以不会失败
+    pub fn new(pattern: &str) -> Glob {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    let at_start = i == 0 || chars[i - 1] == '/';
+                    i += 2;
+                    if at_start && chars.get(i) == Some(&'/') {
+                        tokens.push(Token::AnyDirs);
+                        i += 1;
+                    } else {
+                        tokens.push(Token::AnyPath);
+                    }
+                    continue;
+                }
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::Any),
+                '[' => {
+                    if let Some((token, next)) = parse_class(&chars, i + 1) {
+                        tokens.push(token);
+                        i = next;
+                        continue;
+                    }
+                    tokens.push(Token::Literal('['));
+                }
+                '\\' if i + 1 < chars.len() => {
+                    i += 1;
+                    tokens.push(Token::Literal(chars[i]));
+                }
+                c => tokens.push(Token::Literal(c)),
+            }
+            i += 1;
+        }
+        Glob {
+            pattern: pattern.to_string(),
+            tokens,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 匹配整个路径，路径分隔符使用 `/`
+    pub fn is_match(&self, path: &str) -> bool {
+        let text: Vec<char> = path.chars().collect();
+        matches(&self.tokens, &text)
+    }
+}
+
+fn parse_class(chars: &[char], mut i: usize) -> Option<(Token, usize)> {
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let mut c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Token::Class { ranges, negated }, i + 1));
+        }
+        first = false;
+        if c == '\\' {
+            i += 1;
+            c = *chars.get(i)?;
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&n| n != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+/// 从后往前填表：`next[i]` 表示 `tokens[t + 1..]` 能否匹配 `text[i..]`，
+/// 由它算出 `tokens[t..]` 这一行。每个 token 只扫一遍文本，`*` 再多也不会回溯成指数级
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    let n = text.len();
+    let mut next: Vec<bool> = (0..=n).map(|i| i == n).collect();
+    let mut cur = vec![false; n + 1];
+    for token in tokens.iter().rev() {
+        // `**/` 用：`i` 之后有没有一个 `/`，它后面的部分能匹配剩下的 token
+        let mut dir_after = false;
+        for i in (0..=n).rev() {
+            let c = text.get(i).copied();
+            cur[i] = match token {
+                Token::Literal(want) => c == Some(*want) && next[i + 1],
+                Token::Any => c.is_some_and(|c| c != '/') && next[i + 1],
+                Token::Class { ranges, negated } => {
+                    c.is_some_and(|c| {
+                        c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+                    }) && next[i + 1]
+                }
+                Token::Star => next[i] || (c.is_some_and(|c| c != '/') && cur[i + 1]),
+                Token::AnyPath => next[i] || (c.is_some() && cur[i + 1]),
+                Token::AnyDirs => {
+                    dir_after = dir_after || (c == Some('/') && next[i + 1]);
+                    next[i] || dir_after
+                }
+            };
+        }
+        std::mem::swap(&mut cur, &mut next);
+    }
+    next[0]
+}
@@ -0,0 +1,181 @@
+//! gzip（RFC 1952）格式的解压，用来直接搜索 `.gz` 文件。
+//!
+//! 是不是 gzip 只看开头的魔数 `1f 8b`，和文件名无关，所以管道里的 stdin 也能识别。
+//! 一个文件可以由几个 gzip 成员首尾相接（`cat a.gz b.gz`），解压结果也是依次相接；
+//! 每个成员结尾的 CRC32 和长度都会校验，不一致时报错。
+
+use std::io::{self, BufRead, Read};
+
+use super::inflate::{invalid, BitReader, Inflate};
+
+const MAGIC: [u8; 2] = [0x1F, 0x8B];
+/// 压缩方法只有 8（DEFLATE）
+const DEFLATE: u8 = 8;
+
+const FHCRC: u8 = 1 << 1;
+const FEXTRA: u8 = 1 << 2;
+const FNAME: u8 = 1 << 3;
+const FCOMMENT: u8 = 1 << 4;
+const RESERVED: u8 = 0xE0;
+
+/// 开头是不是 gzip 的魔数
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            k += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// gzip 用的 CRC-32（和 zlib、PNG 相同）
+///
+/// ```
+/// use learning_rust::mgrep::gzip::crc32;
+///
+/// assert_eq!(0xCBF4_3926, crc32(0, b"123456789"));
+/// ```
+pub fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in bytes {
+        crc = CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[derive(Debug)]
+enum State {
+    /// 下一步读成员头，`bool` 表示是不是第一个成员
+    Header(bool),
+    Body,
+    Done,
+}
+
+/// 把 gzip 数据解压成 `Read`
+///
+/// ```
+/// use std::io::Read;
+/// use learning_rust::mgrep::gzip::GzDecoder;
+///
+/// // `printf hi | gzip -n`
+/// let data = [
+///     0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0xc8, 0x04, 0x00,
+///     0xac, 0x2a, 0x93, 0xd8, 0x02, 0x00, 0x00, 0x00,
+/// ];
+/// let mut text = String::new();
+/// GzDecoder::new(&data[..]).read_to_string(&mut text).unwrap();
+/// assert_eq!("hi", text);
+/// ```
+#[derive(Debug)]
+pub struct GzDecoder<R> {
+    input: BitReader<R>,
+    inflate: Inflate,
+    state: State,
+    crc: u32,
+    size: u32,
+}
+
+impl<R: BufRead> GzDecoder<R> {
+    pub fn new(input: R) -> GzDecoder<R> {
+        GzDecoder {
+            input: BitReader::new(input),
+            inflate: Inflate::new(),
+            state: State::Header(true),
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        let input = &mut self.input;
+        if input.le(2)? != u16::from_le_bytes(MAGIC) as u32 {
+            return Err(invalid("not in gzip format"));
+        }
+        if input.byte()? != DEFLATE {
+            return Err(invalid("unknown gzip compression method"));
+        }
+        let flags = input.byte()?;
+        if flags & RESERVED != 0 {
+            return Err(invalid("unknown gzip flags"));
+        }
+        // MTIME、XFL、OS
+        input.le(4)?;
+        input.le(2)?;
+        if flags & FEXTRA != 0 {
+            let len = input.le(2)?;
+            for _ in 0..len {
+                input.byte()?;
+            }
+        }
+        for flag in [FNAME, FCOMMENT] {
+            if flags & flag != 0 {
+                while input.byte()? != 0 {}
+            }
+        }
+        if flags & FHCRC != 0 {
+            input.le(2)?;
+        }
+        Ok(())
+    }
+
+    fn trailer(&mut self) -> io::Result<()> {
+        let crc = self.input.le(4)?;
+        let size = self.input.le(4)?;
+        if crc != self.crc {
+            return Err(invalid("gzip CRC mismatch"));
+        }
+        if size != self.size {
+            return Err(invalid("gzip length mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.state {
+                State::Header(first) => {
+                    if !first && self.input.is_eof()? {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    self.header()?;
+                    self.inflate = Inflate::new();
+                    self.crc = 0;
+                    self.size = 0;
+                    self.state = State::Body;
+                }
+                State::Body => {
+                    let n = self.inflate.read(&mut self.input, buf)?;
+                    if n > 0 || buf.is_empty() {
+                        self.crc = crc32(self.crc, &buf[..n]);
+                        // ISIZE 是长度对 2^32 取模
+                        self.size = self.size.wrapping_add(n as u32);
+                        return Ok(n);
+                    }
+                    self.trailer()?;
+                    self.state = State::Header(false);
+                }
+                State::Done => return Ok(0),
+            }
+        }
+    }
+}
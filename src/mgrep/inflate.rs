@@ -0,0 +1,433 @@
+//! DEFLATE（RFC 1951）解压，给 `gzip` 模块用。
+//!
+//! 按块解码：存储块直接复制，压缩块用规范 Huffman 码解出字面量和 (长度, 距离) 对。
+//! 解码时先查 `FAST_BITS` 位的表，码长更长的少数符号再按码长逐位比较。
+//! 解出来的数据放在 `out` 里，同时作为回溯引用的窗口，读走以后只保留最后 32KB，
+//! 所以解压任意大的文件内存占用也是固定的。
+
+use std::io::{self, BufRead};
+
+/// 回溯引用最远的距离
+const WINDOW: usize = 32 * 1024;
+/// 每次 `read` 最多先解出这么多字节
+const CHUNK: usize = 32 * 1024;
+/// 码长最大是 15 位
+const MAX_BITS: usize = 15;
+/// 查表一次能解出的码长
+const FAST_BITS: u32 = 10;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// 动态块里码长表的码长的顺序
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// 低位在前地从字节流里读比特
+#[derive(Debug)]
+pub struct BitReader<R> {
+    inner: R,
+    bits: u64,
+    nbits: u32,
+}
+
+impl<R: BufRead> BitReader<R> {
+    pub fn new(inner: R) -> BitReader<R> {
+        BitReader {
+            inner,
+            bits: 0,
+            nbits: 0,
+        }
+    }
+
+    /// 尽量把缓冲补到 56 位以上，输入结束时可能不够
+    fn refill(&mut self) -> io::Result<()> {
+        while self.nbits <= 56 {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let n = buf.len().min(((64 - self.nbits) / 8) as usize);
+            for &b in &buf[..n] {
+                self.bits |= (b as u64) << self.nbits;
+                self.nbits += 8;
+            }
+            self.inner.consume(n);
+        }
+        Ok(())
+    }
+
+    /// 看一眼接下来的 `n` 位，输入不够时高位补 0
+    fn peek(&mut self, n: u32) -> io::Result<u32> {
+        if self.nbits < n {
+            self.refill()?;
+        }
+        Ok((self.bits & ((1u64 << n) - 1)) as u32)
+    }
+
+    fn skip(&mut self, n: u32) -> io::Result<()> {
+        if n > self.nbits {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "compressed data is truncated",
+            ));
+        }
+        self.bits >>= n;
+        self.nbits -= n;
+        Ok(())
+    }
+
+    pub fn bits(&mut self, n: u32) -> io::Result<u32> {
+        let value = self.peek(n)?;
+        self.skip(n)?;
+        Ok(value)
+    }
+
+    /// 丢掉不满一个字节的部分
+    pub fn align(&mut self) {
+        self.bits >>= self.nbits % 8;
+        self.nbits -= self.nbits % 8;
+    }
+
+    /// 对齐到字节以后读一个字节
+    pub fn byte(&mut self) -> io::Result<u8> {
+        self.align();
+        Ok(self.bits(8)? as u8)
+    }
+
+    /// 对齐到字节以后读一个小端的 `u16` / `u32`
+    pub fn le(&mut self, bytes: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..bytes {
+            value |= (self.byte()? as u32) << (8 * i);
+        }
+        Ok(value)
+    }
+
+    /// 输入是否已经全部读完
+    pub fn is_eof(&mut self) -> io::Result<bool> {
+        Ok(self.nbits < 8 && self.inner.fill_buf()?.is_empty())
+    }
+}
+
+/// 规范 Huffman 码的解码表
+#[derive(Debug)]
+struct Huffman {
+    /// 按低位在前的 `FAST_BITS` 位索引，值是 `符号 << 4 | 码长`，码长为 0 表示要走慢路径
+    fast: Vec<u16>,
+    /// 每个码长的符号个数
+    counts: [u16; MAX_BITS + 1],
+    /// 按码长、再按符号排好序的符号
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        // 码不能超额；不完整的码只允许出现在只有一个距离码之类的情况，解码时遇到空位再报错
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(invalid("invalid Huffman code lengths"));
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        let mut fast = vec![0u16; 1 << FAST_BITS];
+        let mut code = 0u32;
+        let mut index = 0;
+        for (len, &count) in counts
+            .iter()
+            .enumerate()
+            .take(FAST_BITS as usize + 1)
+            .skip(1)
+        {
+            for _ in 0..count {
+                let reversed = code.reverse_bits() >> (32 - len);
+                let entry = symbols[index] << 4 | len as u16;
+                let mut i = reversed as usize;
+                while i < fast.len() {
+                    fast[i] = entry;
+                    i += 1 << len;
+                }
+                code += 1;
+                index += 1;
+            }
+            code <<= 1;
+        }
+
+        Ok(Huffman {
+            fast,
+            counts,
+            symbols,
+        })
+    }
+
+    fn decode<R: BufRead>(&self, input: &mut BitReader<R>) -> io::Result<u16> {
+        let bits = input.peek(MAX_BITS as u32)?;
+        let entry = self.fast[(bits & ((1 << FAST_BITS) - 1)) as usize];
+        if entry & 0xF != 0 {
+            input.skip((entry & 0xF) as u32)?;
+            return Ok(entry >> 4);
+        }
+
+        // 按码长逐位比较，和 zlib 的 puff.c 一样
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= ((bits >> (len - 1)) & 1) as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                input.skip(len as u32)?;
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("invalid Huffman code"))
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    /// 下一步读块头
+    Header,
+    /// 存储块还剩多少字节
+    Stored(usize),
+    /// 压缩块，字面量/长度码和距离码
+    Codes(Box<(Huffman, Huffman)>),
+    Done,
+}
+
+/// 从 `BitReader` 里解压一个 DEFLATE 流
+#[derive(Debug)]
+pub struct Inflate {
+    state: State,
+    last_block: bool,
+    out: Vec<u8>,
+    /// `out` 里已经被读走的位置
+    pos: usize,
+}
+
+impl Inflate {
+    pub fn new() -> Inflate {
+        Inflate {
+            state: State::Header,
+            last_block: false,
+            out: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// 解出一部分数据放进 `buf`，流结束时返回 0
+    pub fn read<R: BufRead>(
+        &mut self,
+        input: &mut BitReader<R>,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        if self.pos == self.out.len() {
+            if self.out.len() > 2 * WINDOW {
+                self.out.drain(..self.out.len() - WINDOW);
+                self.pos = self.out.len();
+            }
+            while self.out.len() - self.pos < CHUNK && !matches!(self.state, State::Done) {
+                self.step(input)?;
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+
+    /// 往前解一段，最多解到 `out` 里有 `CHUNK` 字节没被读走
+    fn step<R: BufRead>(&mut self, input: &mut BitReader<R>) -> io::Result<()> {
+        match &mut self.state {
+            State::Header if self.last_block => self.state = State::Done,
+            State::Header => self.state = self.block_header(input)?,
+            State::Stored(left) => {
+                let n = (*left).min(CHUNK);
+                for _ in 0..n {
+                    self.out.push(input.byte()?);
+                }
+                *left -= n;
+                if *left == 0 {
+                    self.state = State::Header;
+                }
+            }
+            State::Codes(codes) => {
+                let (lit, dist) = &**codes;
+                let limit = self.pos + CHUNK;
+                while self.out.len() < limit {
+                    let symbol = lit.decode(input)? as usize;
+                    if symbol < 256 {
+                        self.out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        self.state = State::Header;
+                        return Ok(());
+                    }
+                    let i = symbol - 257;
+                    if i >= LENGTH_BASE.len() {
+                        return Err(invalid("invalid length code"));
+                    }
+                    let length =
+                        LENGTH_BASE[i] as usize + input.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                    let i = dist.decode(input)? as usize;
+                    if i >= DIST_BASE.len() {
+                        return Err(invalid("invalid distance code"));
+                    }
+                    let distance =
+                        DIST_BASE[i] as usize + input.bits(DIST_EXTRA[i] as u32)? as usize;
+                    if distance > self.out.len() {
+                        return Err(invalid("distance too far back"));
+                    }
+                    // 距离可能比长度短，这时复制的是刚写出来的字节，只能逐个复制
+                    let from = self.out.len() - distance;
+                    for k in 0..length {
+                        let b = self.out[from + k];
+                        self.out.push(b);
+                    }
+                }
+            }
+            State::Done => {}
+        }
+        Ok(())
+    }
+
+    fn block_header<R: BufRead>(&mut self, input: &mut BitReader<R>) -> io::Result<State> {
+        self.last_block = input.bits(1)? == 1;
+        match input.bits(2)? {
+            0 => {
+                input.align();
+                let len = input.le(2)?;
+                let nlen = input.le(2)?;
+                if len != !nlen & 0xFFFF {
+                    return Err(invalid("invalid stored block length"));
+                }
+                Ok(if len == 0 {
+                    State::Header
+                } else {
+                    State::Stored(len as usize)
+                })
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let codes = (Huffman::new(&lengths)?, Huffman::new(&[5; 30])?);
+                Ok(State::Codes(Box::new(codes)))
+            }
+            2 => Ok(State::Codes(Box::new(dynamic_codes(input)?))),
+            _ => Err(invalid("invalid block type")),
+        }
+    }
+}
+
+impl Default for Inflate {
+    fn default() -> Inflate {
+        Inflate::new()
+    }
+}
+
+/// 读动态块开头的码长表
+fn dynamic_codes<R: BufRead>(input: &mut BitReader<R>) -> io::Result<(Huffman, Huffman)> {
+    let nlen = input.bits(5)? as usize + 257;
+    let ndist = input.bits(5)? as usize + 1;
+    let ncode = input.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(invalid("too many length or distance codes"));
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &CODE_LENGTH_ORDER[..ncode] {
+        code_lengths[i] = input.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code.decode(input)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 if i == 0 => return Err(invalid("repeat with no previous length")),
+            16 => (lengths[i - 1], 3 + input.bits(2)? as usize),
+            17 => (0, 3 + input.bits(3)? as usize),
+            _ => (0, 11 + input.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(invalid("too many code lengths"));
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid("missing end-of-block code"));
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen])?,
+        Huffman::new(&lengths[nlen..])?,
+    ))
+}
+
+/// 解压整个 DEFLATE 流（不带 gzip 头）
+///
+/// ```
+/// use learning_rust::mgrep::inflate;
+///
+/// // 一个存储块："hi"
+/// let data = [0x01, 0x02, 0x00, 0xFD, 0xFF, b'h', b'i'];
+/// assert_eq!(b"hi".to_vec(), inflate::inflate(&data[..]).unwrap());
+/// ```
+pub fn inflate<R: BufRead>(input: R) -> io::Result<Vec<u8>> {
+    let mut input = BitReader::new(input);
+    let mut inflate = Inflate::new();
+    let mut out = Vec::new();
+    let mut buf = [0; 8 * 1024];
+    loop {
+        let n = inflate.read(&mut input, &mut buf)?;
+        if n == 0 {
+            return Ok(out);
+        }
+        out.extend_from_slice(&buf[..n]);
+    }
+}
@@ -0,0 +1,99 @@
+//! `--json` 的输出格式：JSON Lines，每行一个事件对象。
+//!
+//! 每个对象都有 `type` 字段，其余字段按类型固定，以后只会增加字段，不会删除或者改名：
+//!
+//! - `begin`：开始输出一个文件的结果，只在这个文件有匹配时出现
+//!   `{"type":"begin","path":"src/main.rs"}`
+//! - `match`：选中的一行。`line` 不含换行符；`submatches` 是行内每个匹配，
+//!   `start` / `end` 是相对这一行的字节偏移，`-v` 时为空数组。`--replace` 时 `line` 和
+//!   `submatches` 描述的是替换以后的文本
+//!   `{"type":"match","path":"a.txt","line_number":3,"byte_offset":42,"line":"foo bar","submatches":[{"text":"foo","start":0,"end":3}]}`
+//! - `context`：`-A` / `-B` / `-C` 输出的上下文行，字段和 `match` 相同，但没有 `submatches`
+//! - `end`：一个文件的结果结束，和 `begin` 成对出现
+//!   `{"type":"end","path":"a.txt","stats":{"lines":120,"selected":2,"invalid_utf8":0,"binary":false}}`
+//! - `summary`：最后一行，所有文件的汇总，`elapsed_ms` 是总耗时（毫秒）
+//!   `{"type":"summary","stats":{"files":10,"files_matched":2,"selected":5,"errors":0,"elapsed_ms":3}}`
+//!
+//! 路径来自标准输入时是 `(standard input)`。字符串按 JSON 规则转义，非 ASCII 字符原样输出（UTF-8）。
+
+use std::fmt::{Display, Write};
+
+/// 按顺序拼出一个 JSON 对象
+#[derive(Debug, Clone)]
+pub struct Object(String);
+
+impl Object {
+    pub fn new() -> Object {
+        Object(String::from("{"))
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.0.len() > 1 {
+            self.0.push(',');
+        }
+        push_string(&mut self.0, key);
+        self.0.push(':');
+    }
+
+    pub fn string(mut self, key: &str, value: &str) -> Object {
+        self.key(key);
+        push_string(&mut self.0, value);
+        self
+    }
+
+    /// 数字或者布尔值，直接输出 `Display` 的结果
+    pub fn value(mut self, key: &str, value: impl Display) -> Object {
+        self.key(key);
+        write!(self.0, "{}", value).unwrap();
+        self
+    }
+
+    /// 已经是 JSON 的值，例如嵌套的对象或数组
+    pub fn raw(mut self, key: &str, json: &str) -> Object {
+        self.key(key);
+        self.0.push_str(json);
+        self
+    }
+
+    pub fn finish(mut self) -> String {
+        self.0.push('}');
+        self.0
+    }
+}
+
+impl Default for Object {
+    fn default() -> Object {
+        Object::new()
+    }
+}
+
+/// 把多个已经是 JSON 的值拼成数组
+pub fn array<I: IntoIterator<Item = String>>(items: I) -> String {
+    let items: Vec<String> = items.into_iter().collect();
+    format!("[{}]", items.join(","))
+}
+
+/// 转义并加上引号
+pub fn string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    push_string(&mut out, value);
+    out
+}
+
+fn push_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7F}' => {
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
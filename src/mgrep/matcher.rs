@@ -0,0 +1,326 @@
+//! 匹配器：在一行文本里找出所有匹配的位置。
+
+use super::{
+    aho_corasick::AhoCorasick,
+    fuzzy::Fuzzy,
+    regex::{self, Regex},
+    unicode, Config,
+};
+
+/// 各个捕获组的字节区间，第 0 组是整个匹配
+pub type Groups = Vec<Option<(usize, usize)>>;
+
+/// 在单行文本里查找匹配，返回的区间都是相对这一行的字节偏移
+///
+/// 多线程搜索时同一个匹配器会被多个线程共享，所以要求 `Send + Sync`。
+pub trait Matcher: Send + Sync {
+    /// 从 `start` 开始查找下一个匹配
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)>;
+
+    /// 从 `start` 开始查找下一个匹配以及各个捕获组
+    ///
+    /// 默认只有第 0 组，支持捕获组的匹配器需要同时覆盖 `group_index`。
+    fn captures_at(&self, line: &str, start: usize) -> Option<Groups> {
+        self.find_at(line, start).map(|span| vec![Some(span)])
+    }
+
+    /// 命名捕获组的序号
+    fn group_index(&self, _name: &str) -> Option<usize> {
+        None
+    }
+
+    /// 这一行和 query 的差距，用来给结果排序；只有 `--fuzzy` 的匹配器会返回 `Some`
+    fn distance(&self, _line: &str) -> Option<usize> {
+        None
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// 所有不重叠的匹配；空匹配之后跳过一个字符，避免死循环
+    fn find_iter(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while start <= line.len() {
+            let (s, e) = match self.find_at(line, start) {
+                Some(span) => span,
+                None => break,
+            };
+            spans.push((s, e));
+            start = if e > s {
+                e
+            } else {
+                match line[e..].chars().next() {
+                    Some(c) => e + c.len_utf8(),
+                    None => break,
+                }
+            };
+        }
+        spans
+    }
+}
+
+/// 普通字符串匹配
+///
+/// 忽略大小写时逐个字符做 Unicode 简单大小写折叠后比较，不会为每一行分配新的字符串。
+#[derive(Debug, Clone)]
+pub struct Literal {
+    query: String,
+    case_sensitive: bool,
+    /// 折叠过的 query，只在忽略大小写时使用
+    folded: Vec<char>,
+}
+
+impl Literal {
+    pub fn new(query: &str, case_sensitive: bool) -> Literal {
+        Literal {
+            query: query.to_string(),
+            case_sensitive,
+            folded: query.chars().map(unicode::simple_fold).collect(),
+        }
+    }
+
+    /// 忽略大小写时，`line[at..]` 开头能否匹配上 query，返回匹配的结尾
+    fn match_folded(&self, line: &str, at: usize) -> Option<usize> {
+        let mut text = line[at..].char_indices();
+        for &q in &self.folded {
+            let (_, c) = text.next()?;
+            if unicode::simple_fold(c) != q {
+                return None;
+            }
+        }
+        Some(at + text.next().map_or(line.len() - at, |(i, _)| i))
+    }
+}
+
+impl Matcher for Literal {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        if self.case_sensitive {
+            let s = start + line[start..].find(&self.query)?;
+            return Some((s, s + self.query.len()));
+        }
+        let first = match self.folded.first() {
+            Some(&first) => first,
+            None => return Some((start, start)),
+        };
+        line[start..]
+            .char_indices()
+            .filter(|&(_, c)| unicode::simple_fold(c) == first)
+            .find_map(|(i, _)| {
+                let at = start + i;
+                self.match_folded(line, at).map(|end| (at, end))
+            })
+    }
+}
+
+/// `-w`：只接受前后都不是单词字符的匹配
+///
+/// 和 grep 一样，某个位置的匹配不满足条件时，从它的下一个字符开始继续找。
+pub struct WordMatcher<M> {
+    inner: M,
+}
+
+impl<M: Matcher> WordMatcher<M> {
+    pub fn new(inner: M) -> WordMatcher<M> {
+        WordMatcher { inner }
+    }
+}
+
+impl<M: Matcher> WordMatcher<M> {
+    /// 用 `find` 从 `start` 开始找，跳过前后紧挨着单词字符的匹配
+    fn next_word<T>(
+        line: &str,
+        mut start: usize,
+        find: impl Fn(usize) -> Option<T>,
+        span: impl Fn(&T) -> Option<(usize, usize)>,
+    ) -> Option<T> {
+        loop {
+            let found = find(start)?;
+            let (s, e) = span(&found)?;
+            let before = line[..s].chars().next_back();
+            let after = line[e..].chars().next();
+            if !before.is_some_and(unicode::is_word_char)
+                && !after.is_some_and(unicode::is_word_char)
+            {
+                return Some(found);
+            }
+            start = s + line[s..].chars().next()?.len_utf8();
+        }
+    }
+}
+
+impl<M: Matcher> Matcher for WordMatcher<M> {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        Self::next_word(
+            line,
+            start,
+            |at| self.inner.find_at(line, at),
+            |&span| Some(span),
+        )
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Groups> {
+        Self::next_word(
+            line,
+            start,
+            |at| self.inner.captures_at(line, at),
+            |groups| groups[0],
+        )
+    }
+
+    fn group_index(&self, name: &str) -> Option<usize> {
+        self.inner.group_index(name)
+    }
+}
+
+impl<M: Matcher + ?Sized> Matcher for Box<M> {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        (**self).find_at(line, start)
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Groups> {
+        (**self).captures_at(line, start)
+    }
+
+    fn group_index(&self, name: &str) -> Option<usize> {
+        (**self).group_index(name)
+    }
+
+    fn distance(&self, line: &str) -> Option<usize> {
+        (**self).distance(line)
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        Regex::find_at(self, line, start)
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Groups> {
+        let caps = Regex::captures_at(self, line, start)?;
+        Some((0..caps.len()).map(|i| caps.pos(i)).collect())
+    }
+
+    fn group_index(&self, name: &str) -> Option<usize> {
+        Regex::group_index(self, name)
+    }
+}
+
+/// 多个 `-e` 正则表达式：合并成一个分支选择只扫描一遍，捕获组仍然按各自的模式编号
+///
+/// 合并时每个模式包在一个捕获组里，`(p1)|(p2)`，哪个组参与了匹配就是哪个模式匹配到的，
+/// 再取出它自己的捕获组，所以 `--replace '$1'` 总是引用匹配到的那个模式的第 1 组。
+/// 行内标志 `(?i)` 只作用到所在的组结束，不会影响别的模式。
+/// 命名捕获组按第一个定义了这个名字的模式编号。
+pub struct RegexSet {
+    regex: Regex,
+    patterns: Vec<Regex>,
+}
+
+impl RegexSet {
+    pub fn new(patterns: &[String], case_sensitive: bool) -> Result<RegexSet, regex::Error> {
+        let compile = |pattern: &str| {
+            if case_sensitive {
+                Regex::new(pattern)
+            } else {
+                Regex::new_case_insensitive(pattern)
+            }
+        };
+        // 先单独编译每个模式，出错时报告的位置才对得上
+        let patterns = patterns
+            .iter()
+            .map(|pattern| compile(pattern))
+            .collect::<Result<Vec<_>, _>>()?;
+        let groups: Vec<String> = patterns
+            .iter()
+            .map(|pattern| format!("({})", pattern.as_str()))
+            .collect();
+        let regex = compile(&groups.join("|"))?;
+        Ok(RegexSet { regex, patterns })
+    }
+}
+
+impl Matcher for RegexSet {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        self.regex.find_at(line, start)
+    }
+
+    fn captures_at(&self, line: &str, start: usize) -> Option<Groups> {
+        let caps = self.regex.captures_at(line, start)?;
+        // 第 `outer` 组包着当前模式，它自己的组紧跟在后面
+        let mut outer = 1;
+        for pattern in &self.patterns {
+            let len = pattern.captures_len();
+            if caps.pos(outer).is_some() {
+                let inner = (outer + 1..outer + len).map(|i| caps.pos(i));
+                return Some(std::iter::once(caps.pos(0)).chain(inner).collect());
+            }
+            outer += len;
+        }
+        None
+    }
+
+    fn group_index(&self, name: &str) -> Option<usize> {
+        self.patterns
+            .iter()
+            .find_map(|pattern| pattern.group_index(name))
+    }
+}
+
+impl Matcher for AhoCorasick {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        AhoCorasick::find_at(self, line, start)
+    }
+}
+
+impl Matcher for Fuzzy {
+    fn find_at(&self, line: &str, start: usize) -> Option<(usize, usize)> {
+        Fuzzy::find_at(self, line, start)
+    }
+
+    fn distance(&self, line: &str) -> Option<usize> {
+        Fuzzy::distance(self, line)
+    }
+}
+
+/// 根据配置选出匹配器
+///
+/// 多个普通字符串用 Aho-Corasick 一遍扫描；多个正则表达式用 `RegexSet` 合并成一个分支选择，
+/// 同样只扫描一遍。没有任何模式（例如 `-f` 指定了空文件）时什么都不匹配。
+pub fn from_config(config: &Config) -> Result<Box<dyn Matcher>, regex::Error> {
+    let patterns = &config.patterns;
+    let matcher: Box<dyn Matcher> = if let (Some(distance), [query]) = (config.fuzzy, &patterns[..])
+    {
+        Box::new(Fuzzy::new(query, distance, config.case_sensitive))
+    } else if let (true, [pattern]) = (config.regex, &patterns[..]) {
+        Box::new(if config.case_sensitive {
+            Regex::new(pattern)?
+        } else {
+            Regex::new_case_insensitive(pattern)?
+        })
+    } else if config.regex && !patterns.is_empty() {
+        Box::new(RegexSet::new(patterns, config.case_sensitive)?)
+    } else if let [pattern] = &patterns[..] {
+        Box::new(Literal::new(pattern, config.case_sensitive))
+    } else {
+        Box::new(AhoCorasick::new(patterns, config.case_sensitive))
+    };
+    Ok(if config.word {
+        Box::new(WordMatcher::new(matcher))
+    } else {
+        matcher
+    })
+}
+
+/// 转义正则表达式里的特殊字符
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,185 @@
+//! 快速的字节查找和子串查找。
+//!
+//! `memchr` / `memrchr` 每次比较一个机器字（SWAR）：把要找的字节复制到字里的每个字节，
+//! 异或以后检查有没有为零的字节，一次就能排除 8 个字节，编译器也容易把它向量化。
+//!
+//! `Finder` 先用 `memchr` 找子串里最少见的那个字节，命中后再比较整个子串。
+//! 这个字节其实很常见、误报太多时，改为按字检查最少见的两个字节是否同时出现在对应的位置上；
+//! 还是太多时（例如在 `aaaa…` 里找 `aaab`），剩下的部分交给标准库的 `str::find`（Two-Way 算法），
+//! 保证最坏情况也是线性时间。
+
+use std::mem::size_of;
+
+const WORD: usize = size_of::<usize>();
+/// 每个字节都是 0x01
+const LO: usize = usize::MAX / 255;
+/// 每个字节都是 0x80
+const HI: usize = LO * 0x80;
+
+/// 字里是否有为零的字节
+fn has_zero(x: usize) -> bool {
+    x.wrapping_sub(LO) & !x & HI != 0
+}
+
+fn contains_byte(chunk: &[u8], repeated: usize) -> bool {
+    let (a, b) = chunk.split_at(WORD);
+    let a = usize::from_ne_bytes(a.try_into().unwrap());
+    let b = usize::from_ne_bytes(b.try_into().unwrap());
+    has_zero(a ^ repeated) || has_zero(b ^ repeated)
+}
+
+/// 字里为零的字节对应的最高位置 1，其余位为 0
+fn zero_mask(x: usize) -> usize {
+    !(((x & !HI).wrapping_add(!HI)) | x) & HI
+}
+
+fn load(bytes: &[u8], at: usize) -> usize {
+    usize::from_le_bytes(bytes[at..at + WORD].try_into().unwrap())
+}
+
+/// `needle` 在 `haystack` 里第一次出现的位置
+pub fn memchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO * needle as usize;
+    let mut start = 0;
+    for chunk in haystack.chunks_exact(2 * WORD) {
+        if contains_byte(chunk, repeated) {
+            break;
+        }
+        start += 2 * WORD;
+    }
+    haystack[start..]
+        .iter()
+        .position(|&b| b == needle)
+        .map(|i| start + i)
+}
+
+/// `needle` 在 `haystack` 里最后一次出现的位置
+pub fn memrchr(needle: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO * needle as usize;
+    let mut end = haystack.len();
+    for chunk in haystack.rchunks_exact(2 * WORD) {
+        if contains_byte(chunk, repeated) {
+            break;
+        }
+        end -= 2 * WORD;
+    }
+    haystack[..end].iter().rposition(|&b| b == needle)
+}
+
+/// 字节在一般文本和代码里大致的常见程度，越大越常见
+fn rank(b: u8) -> u8 {
+    match b {
+        b' ' => 255,
+        b'e' => 240,
+        b't' | b'a' | b'o' => 230,
+        b'i' | b'n' | b's' | b'r' => 220,
+        b'h' | b'l' | b'd' => 200,
+        b'c' | b'u' | b'm' | b'\n' => 180,
+        b'f' | b'p' | b'g' | b'w' | b'y' | b'\t' => 160,
+        b'b' | b'v' | b'k' | b'.' | b',' | b'_' | b'(' | b')' | b';' | b'=' => 140,
+        b'a'..=b'z' | b'0'..=b'9' => 100,
+        b'A'..=b'Z' => 80,
+        0x80..=0xFF => 70,
+        _ if b.is_ascii_punctuation() => 60,
+        _ => 0,
+    }
+}
+
+/// 在文本里查找固定的子串
+///
+/// ```
+/// use learning_rust::mgrep::memchr::Finder;
+///
+/// let finder = Finder::new("duct");
+/// assert_eq!(Some(15), finder.find("safe, fast, productive."));
+/// assert_eq!(None, finder.find_at("safe, fast, productive.", 16));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: String,
+    /// `needle` 里最少见和第二少见的字节的位置，`needle` 只有一个字节时两个相同
+    rare: (usize, usize),
+}
+
+impl Finder {
+    pub fn new(needle: &str) -> Finder {
+        let rarest = |skip: Option<usize>| {
+            needle
+                .bytes()
+                .enumerate()
+                .filter(|&(i, _)| Some(i) != skip)
+                .min_by_key(|&(_, b)| rank(b))
+                .map(|(i, _)| i)
+        };
+        let first = rarest(None).unwrap_or(0);
+        let second = rarest(Some(first)).unwrap_or(first);
+        Finder {
+            needle: needle.to_string(),
+            rare: (first, second),
+        }
+    }
+
+    pub fn needle(&self) -> &str {
+        &self.needle
+    }
+
+    pub fn find(&self, haystack: &str) -> Option<usize> {
+        self.find_at(haystack, 0)
+    }
+
+    /// 从 `start` 开始查找，返回匹配开头的字节偏移
+    pub fn find_at(&self, haystack: &str, start: usize) -> Option<usize> {
+        let (text, needle) = (haystack.as_bytes(), self.needle.as_bytes());
+        let last = text.len().checked_sub(needle.len())?;
+        if start > last {
+            return None;
+        }
+        if needle.len() <= 1 {
+            return match needle.first() {
+                Some(&b) => memchr(b, &text[start..]).map(|i| start + i),
+                None => Some(start),
+            };
+        }
+
+        let (i1, i2) = self.rare;
+        let is_match = |at: usize| &text[at..at + needle.len()] == needle;
+        // 误报平均每 32 个字节就有一次，说明选的字节并不少见
+        let too_many = |misses: usize, scanned: usize| misses > 16 && misses * 32 > scanned;
+
+        let mut pos = start;
+        let mut misses = 0;
+        while pos <= last && !too_many(misses, pos - start) {
+            let candidate = pos + memchr(needle[i1], &text[pos + i1..=last + i1])?;
+            if is_match(candidate) {
+                return Some(candidate);
+            }
+            pos = candidate + 1;
+            misses += 1;
+        }
+
+        // 一次检查 `pos..pos + WORD` 这些开头位置
+        let (r1, r2) = (LO * needle[i1] as usize, LO * needle[i2] as usize);
+        misses = 0;
+        let restart = pos;
+        while pos + WORD <= last + 1 {
+            let mut mask =
+                zero_mask(load(text, pos + i1) ^ r1) & zero_mask(load(text, pos + i2) ^ r2);
+            while mask != 0 {
+                let candidate = pos + mask.trailing_zeros() as usize / 8;
+                if is_match(candidate) {
+                    return Some(candidate);
+                }
+                misses += 1;
+                mask &= mask - 1;
+            }
+            pos += WORD;
+            if too_many(misses, pos - restart) {
+                while !haystack.is_char_boundary(pos) {
+                    pos += 1;
+                }
+                return haystack[pos..].find(&self.needle).map(|i| pos + i);
+            }
+        }
+        (pos..=last).find(|&at| text[at + i1] == needle[i1] && is_match(at))
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    error::Error,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc},
+    thread,
+    time::Instant,
+};
+
+use crate::tpool::ThreadPool;
+
+pub mod aho_corasick;
+pub mod args;
+pub mod color;
+pub mod edit;
+pub mod filter;
+pub mod fuzzy;
+pub mod glob;
+pub mod gzip;
+pub mod inflate;
+pub mod json;
+pub mod matcher;
+pub mod memchr;
+pub mod printer;
+pub mod regex;
+pub mod replace;
+pub mod searcher;
+pub mod unicode;
+pub mod walk;
+
+pub use args::{ColorChoice, Config, ConfigError};
+use color::Colors;
+use filter::Filter;
+use matcher::Matcher;
+use printer::Printer;
+use regex::Regex;
+use replace::Replacer;
+use searcher::{Searcher, Stats, Summary};
+use walk::Walk;
+
+/// 表示标准输入的路径
+pub const STDIN: &str = "-";
+
+/// grep 风格的退出状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// 有选中的行（`-L` 时是有文件被列出）
+    Matched,
+    NoMatch,
+    /// 出现过错误，例如文件不存在；`-q` 时只要有匹配就不算错误
+    Error,
+}
+
+impl Status {
+    /// 进程退出码：0 匹配，1 没有匹配，2 出错
+    pub fn code(self) -> i32 {
+        match self {
+            Status::Matched => 0,
+            Status::NoMatch => 1,
+            Status::Error => 2,
+        }
+    }
+}
+
+pub fn run(config: Config) -> Result<Status, Box<dyn Error>> {
+    // 是否上色由我们自己判断，colored 只负责生成转义序列
+    colored::control::set_override(color::enabled(config.color));
+
+    let matcher: Arc<dyn Matcher> = Arc::from(matcher::from_config(&config)?);
+
+    // 没有给出路径时读 stdin
+    let paths = if config.paths.is_empty() {
+        vec![PathBuf::from(STDIN)]
+    } else {
+        config.paths.clone()
+    };
+
+    // 多个路径或者目录时，每一行匹配前面都加上文件路径
+    let with_filename = paths.len() > 1 || paths.iter().any(|p| p.is_dir());
+    let colors = Colors::from_env();
+    let mut printer =
+        Printer::new(io::stdout().lock(), &config, with_filename).colors(colors.clone());
+    let mut outcome = Outcome::default();
+    let started = Instant::now();
+
+    let filter = Filter::from_config(&config);
+    let walk_errors = Cell::new(0);
+    let files = paths.iter().flat_map(|root| {
+        let walk: Box<dyn Iterator<Item = _>> = if root == Path::new(STDIN) {
+            Box::new(std::iter::once(Ok(root.clone())))
+        } else {
+            Box::new(Walk::new(root).with_filter(filter.clone()))
+        };
+        let walk_errors = &walk_errors;
+        walk.filter_map(move |path| match path {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("minigrep: {}: {}", root.display(), e);
+                walk_errors.set(walk_errors.get() + 1);
+                None
+            }
+        })
+    });
+
+    let result = search_all(
+        &config,
+        &matcher,
+        files,
+        with_filename,
+        colors,
+        &mut printer,
+        &mut outcome,
+    )
+    .and_then(|()| {
+        outcome.summary.errors += walk_errors.get();
+        printer.summary(&outcome.summary, started.elapsed())?;
+        printer.flush()
+    });
+    match result {
+        // 下游（比如 `head`）已经读到了输出并提前关闭了管道，安静地结束
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => outcome.matched = true,
+        result => result?,
+    }
+    Ok(outcome.status(&config))
+}
+
+/// 搜索 `files`，按顺序输出；文件数多于一个时用线程池并行
+fn search_all<W: Write>(
+    config: &Config,
+    matcher: &Arc<dyn Matcher>,
+    files: impl Iterator<Item = PathBuf>,
+    with_filename: bool,
+    colors: Colors,
+    printer: &mut Printer<W>,
+    outcome: &mut Outcome,
+) -> io::Result<()> {
+    let threads = match config.threads {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    };
+    // `--in-place` / `--dry-run` 逐个文件修改
+    if config.in_place || config.dry_run {
+        let replacer = Replacer::new(config.replace.as_deref().unwrap_or_default());
+        for path in files {
+            let mut diff = Vec::new();
+            let result = edit::edit_file(config, matcher.as_ref(), &replacer, &path, &mut diff)
+                .and_then(|stats| printer.append(&diff).map(|()| stats));
+            outcome.record(config, &path, result)?;
+        }
+        return Ok(());
+    }
+    // `-q` 找到第一个匹配就结束，没必要并行
+    if threads == 1 || !with_filename || config.quiet {
+        for path in files {
+            let result = search_file(config, matcher.as_ref(), &path, printer);
+            outcome.record(config, &path, result)?;
+            if config.quiet && outcome.matched {
+                break;
+            }
+        }
+        return Ok(());
+    }
+
+    let pool = ThreadPool::new(threads);
+    let (tx, rx) = mpsc::channel();
+    let mut ordered = Ordered::new();
+    let matcher = matcher.as_ref();
+
+    // job 直接借用 `config` 和 `matcher`，`scope` 返回之前它们都已经结束
+    pool.scope(|s| {
+        for (index, path) in files.enumerate() {
+            let (colors, tx) = (colors.clone(), tx.clone());
+            s.spawn(move || {
+                let mut output = Printer::new(Vec::new(), config, with_filename).colors(colors);
+                let result = search_file(config, matcher, &path, &mut output)
+                    .map(|stats| (output.into_inner(), stats));
+                // 接收端只会在所有结果都收到之后才关闭
+                let _ = tx.send((index, path, result));
+            });
+            // 边遍历边输出已经就绪的结果
+            for result in rx.try_iter() {
+                ordered.push(result, config, printer, outcome)?;
+            }
+        }
+        drop(tx);
+        for result in rx.iter() {
+            ordered.push(result, config, printer, outcome)?;
+        }
+        Ok(())
+    })
+}
+
+/// 汇总所有文件的搜索结果
+#[derive(Debug, Default)]
+struct Outcome {
+    /// 决定退出码：有选中的行，`-L` 时是有文件被列出
+    matched: bool,
+    summary: Summary,
+}
+
+impl Outcome {
+    /// 记录一个文件的结果；只有写 stdout 失败才返回错误，读文件的错误输出到 stderr 后继续
+    fn record(
+        &mut self,
+        config: &Config,
+        path: &Path,
+        result: io::Result<Stats>,
+    ) -> io::Result<()> {
+        match result {
+            Ok(stats) => {
+                self.summary.add(&stats);
+                self.matched |= if config.files_without_match {
+                    stats.selected == 0 && !stats.binary
+                } else {
+                    stats.selected > 0
+                };
+            }
+            // 管道被关闭说明没必要继续搜索，交给调用方处理
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Err(e),
+            Err(e) => {
+                eprintln!("minigrep: {}: {}", path.display(), e);
+                self.summary.errors += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self, config: &Config) -> Status {
+        if self.summary.errors > 0 && !(config.quiet && self.matched) {
+            Status::Error
+        } else if self.matched {
+            Status::Matched
+        } else {
+            Status::NoMatch
+        }
+    }
+}
+
+/// 一个文件在工作线程里的输出和统计
+type FileResult = io::Result<(Vec<u8>, Stats)>;
+type FileOutput = (usize, PathBuf, FileResult);
+
+/// 把乱序完成的搜索结果按文件顺序输出
+struct Ordered {
+    next: usize,
+    pending: BTreeMap<usize, (PathBuf, FileResult)>,
+}
+
+impl Ordered {
+    fn new() -> Ordered {
+        Ordered {
+            next: 0,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    fn push<W: Write>(
+        &mut self,
+        output: FileOutput,
+        config: &Config,
+        printer: &mut Printer<W>,
+        outcome: &mut Outcome,
+    ) -> io::Result<()> {
+        let (index, path, result) = output;
+        self.pending.insert(index, (path, result));
+        while let Some((path, result)) = self.pending.remove(&self.next) {
+            let result = result.and_then(|(output, stats)| {
+                printer.append(&output)?;
+                Ok(stats)
+            });
+            outcome.record(config, &path, result)?;
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+/// 一行匹配结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// 行号，从 1 开始
+    pub line_number: usize,
+    /// 这一行开头在整个文本里的字节偏移
+    pub byte_offset: usize,
+    pub line: &'a str,
+    /// 每个匹配在这一行里的字节区间
+    pub spans: Vec<(usize, usize)>,
+}
+
+impl Match<'_> {
+    /// 第一个匹配所在的列，从 1 开始，按字节计算（和 vim quickfix 一致）
+    pub fn column(&self) -> usize {
+        self.spans.first().map_or(0, |&(start, _)| start) + 1
+    }
+}
+
+/// 按行切分并记录每行的起始偏移，和 `str::lines` 一样去掉 `\n` / `\r\n`
+fn lines_with_offsets(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    contents.split_inclusive('\n').scan(0, |offset, raw| {
+        let start = *offset;
+        *offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Some((start, line))
+    })
+}
+
+/// 找出所有匹配的行以及行内匹配的位置
+pub fn find_matches<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    lines_with_offsets(contents)
+        .enumerate()
+        .filter_map(|(index, (byte_offset, line))| {
+            let spans = matcher.find_iter(line);
+            if spans.is_empty() {
+                return None;
+            }
+            Some(Match {
+                line_number: index + 1,
+                byte_offset,
+                line,
+                spans,
+            })
+        })
+        .collect()
+}
+
+/// 和 `find_matches` 相反，找出所有不匹配的行，`spans` 为空
+pub fn find_non_matches<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    lines_with_offsets(contents)
+        .enumerate()
+        .filter(|(_, (_, line))| !matcher.is_match(line))
+        .map(|(index, (byte_offset, line))| Match {
+            line_number: index + 1,
+            byte_offset,
+            line,
+            spans: Vec::new(),
+        })
+        .collect()
+}
+
+fn search_file<W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    path: &Path,
+    printer: &mut Printer<W>,
+) -> io::Result<Stats> {
+    printer.begin_file(path);
+    let stats = Searcher::from_config(config).search_path(matcher, path, &mut *printer)?;
+    searcher::print_totals(config, &stats, printer)?;
+    printer.end_file(&stats)?;
+    Ok(stats)
+}
+
+/// 找出包含 `query` 的所有行，结果和 `contents.lines().filter(|x| x.contains(query))` 相同
+///
+/// 不逐行切分，而是在整个文本里直接查找 `query`，命中以后才向前后找换行符确定这一行，
+/// 然后从下一行继续。匹配的行很少时，绝大部分文本只被 `memchr` 扫过一遍。
+pub fn search<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
+    if query.is_empty() || query.contains('\n') {
+        return contents.lines().filter(|x| x.contains(query)).collect();
+    }
+    let finder = memchr::Finder::new(query);
+    let bytes = contents.as_bytes();
+    let mut lines = Vec::new();
+    let mut pos = 0;
+    while let Some(hit) = finder.find_at(contents, pos) {
+        let start = memchr::memrchr(b'\n', &bytes[..hit]).map_or(0, |i| i + 1);
+        let (end, next) = match memchr::memchr(b'\n', &bytes[hit..]) {
+            // 和 `str::lines` 一样去掉 `\r\n` 里的 `\r`
+            Some(i) if bytes[..hit + i].ends_with(b"\r") => (hit + i - 1, hit + i + 1),
+            Some(i) => (hit + i, hit + i + 1),
+            None => (bytes.len(), bytes.len()),
+        };
+        // `query` 以 `\r` 结尾时可能匹配到了行尾被去掉的 `\r`
+        if hit + query.len() <= end {
+            lines.push(&contents[start..end]);
+        }
+        pos = next;
+    }
+    lines
+}
+
+pub fn search_case_insensitive<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
+    let query = matcher::Literal::new(query, false);
+    contents.lines().filter(|x| query.is_match(x)).collect()
+}
+
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<&'a str> {
+    contents.lines().filter(|x| re.is_match(x)).collect()
+}
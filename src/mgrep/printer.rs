@@ -0,0 +1,312 @@
+//! 按 grep 的格式输出搜索结果。
+//!
+//! 匹配行用 `:` 分隔前缀，上下文行用 `-` 分隔；开启上下文时，
+//! 不连续的两组输出之间（包括不同文件之间）插入一行 `--`。
+//! `--json` 时改为输出 `json` 模块里描述的事件。
+
+use std::{
+    io::{self, Write},
+    path::Path,
+    time::Duration,
+};
+
+use super::{
+    color::{self, Colors, Style},
+    json::{self, Object},
+    searcher::{Stats, Summary},
+    Config, Match, STDIN,
+};
+
+pub struct Printer<W: Write> {
+    out: W,
+    with_filename: bool,
+    line_number: bool,
+    column: bool,
+    byte_offset: bool,
+    vimgrep: bool,
+    json: bool,
+    context: bool,
+    /// 是否上色，为 `false` 时 `colors` 不起作用
+    color: bool,
+    colors: Colors,
+    path: String,
+    /// 当前文件里最后输出的行号
+    last_line: Option<usize>,
+    /// 是否已经输出过任何一行，用来决定要不要输出 `--`
+    printed: bool,
+    /// `--json` 时当前文件是否已经输出了 `begin`
+    begun: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, config: &Config, with_filename: bool) -> Printer<W> {
+        Printer {
+            out,
+            with_filename: with_filename || config.vimgrep,
+            line_number: config.line_number || config.vimgrep,
+            column: config.column || config.vimgrep,
+            byte_offset: config.byte_offset,
+            vimgrep: config.vimgrep,
+            // `-q` 什么都不输出，包括 `summary`
+            json: config.json && !config.quiet,
+            // `--fuzzy` 按距离排序输出，不输出上下文
+            context: !config.vimgrep
+                && !config.json
+                && (config.fuzzy.is_none() || config.invert_match)
+                && (config.before_context > 0 || config.after_context > 0),
+            color: color::enabled(config.color),
+            colors: Colors::default(),
+            path: String::new(),
+            last_line: None,
+            printed: false,
+            begun: false,
+        }
+    }
+
+    /// 替换默认配色，例如 `Colors::from_env()`
+    pub fn colors(mut self, colors: Colors) -> Printer<W> {
+        self.colors = colors;
+        self
+    }
+
+    pub fn begin_file(&mut self, path: &Path) {
+        self.path = if path == Path::new(STDIN) {
+            String::from("(standard input)")
+        } else {
+            path.display().to_string()
+        };
+        self.last_line = None;
+        self.begun = false;
+    }
+
+    /// 当前文件搜索完毕；`--json` 时输出 `end`
+    pub fn end_file(&mut self, stats: &Stats) -> io::Result<()> {
+        if !self.begun {
+            return Ok(());
+        }
+        let stats = Object::new()
+            .value("lines", stats.lines)
+            .value("selected", stats.selected)
+            .value("invalid_utf8", stats.invalid_utf8)
+            .value("binary", stats.binary)
+            .finish();
+        let event = Object::new()
+            .string("type", "end")
+            .string("path", &self.path)
+            .raw("stats", &stats)
+            .finish();
+        writeln!(self.out, "{}", event)
+    }
+
+    /// 所有文件都搜索完毕；`--json` 时输出 `summary`，否则什么都不做
+    pub fn summary(&mut self, summary: &Summary, elapsed: Duration) -> io::Result<()> {
+        if !self.json {
+            return Ok(());
+        }
+        let stats = Object::new()
+            .value("files", summary.files)
+            .value("files_matched", summary.files_matched)
+            .value("selected", summary.selected)
+            .value("errors", summary.errors)
+            .value("elapsed_ms", elapsed.as_millis())
+            .finish();
+        let event = Object::new()
+            .string("type", "summary")
+            .raw("stats", &stats)
+            .finish();
+        writeln!(self.out, "{}", event)
+    }
+
+    /// 输出一行匹配；`--vimgrep` 时每个匹配单独一行
+    pub fn matched(&mut self, m: &Match) -> io::Result<()> {
+        if self.json {
+            self.begin()?;
+            let submatches = json::array(m.spans.iter().map(|&(start, end)| {
+                Object::new()
+                    .string("text", &m.line[start..end])
+                    .value("start", start)
+                    .value("end", end)
+                    .finish()
+            }));
+            let event = self
+                .event("match", m.line_number, m.byte_offset, m.line)
+                .raw("submatches", &submatches);
+            return writeln!(self.out, "{}", event.finish());
+        }
+        if self.vimgrep {
+            for &(start, _) in &m.spans {
+                let prefix = self.prefix(m.line_number, Some(start + 1), m.byte_offset, ':');
+                let line = self.highlight(m);
+                writeln!(self.out, "{}{}", prefix, line)?;
+            }
+            return Ok(());
+        }
+        self.separator(m.line_number)?;
+        let prefix = self.prefix(m.line_number, Some(m.column()), m.byte_offset, ':');
+        let line = self.highlight(m);
+        if prefix.is_empty() {
+            writeln!(self.out, "match: {}", line)
+        } else {
+            writeln!(self.out, "{}{}", prefix, line)
+        }
+    }
+
+    /// 输出一行上下文
+    pub fn context(
+        &mut self,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        if self.json {
+            self.begin()?;
+            let event = self.event("context", line_number, byte_offset, line);
+            return writeln!(self.out, "{}", event.finish());
+        }
+        self.separator(line_number)?;
+        let prefix = self.prefix(line_number, None, byte_offset, '-');
+        if prefix.is_empty() {
+            writeln!(self.out, "       {}", line)
+        } else {
+            writeln!(self.out, "{}{}", prefix, line)
+        }
+    }
+
+    /// `-c`：输出匹配的行数
+    pub fn count(&mut self, count: usize) -> io::Result<()> {
+        if self.with_filename {
+            let path = self.paint(&self.colors.filename, &self.path);
+            let sep = self.paint(&self.colors.separator, ":");
+            writeln!(self.out, "{}{}{}", path, sep, count)
+        } else {
+            writeln!(self.out, "{}", count)
+        }
+    }
+
+    /// `-l`：输出文件名
+    pub fn path(&mut self) -> io::Result<()> {
+        let path = self.paint(&self.colors.filename, &self.path);
+        writeln!(self.out, "{}", path)
+    }
+
+    /// 不是合法 UTF-8 的行输出到 stderr，`valid_up_to` 是这一行里出错的字节位置
+    pub fn invalid_utf8(&mut self, line_number: usize, valid_up_to: usize) -> io::Result<()> {
+        writeln!(
+            io::stderr(),
+            "minigrep: {}:{}: invalid UTF-8 at byte {}, line skipped",
+            self.path,
+            line_number,
+            valid_up_to
+        )
+    }
+
+    /// 追加另一个 `Printer` 的输出，用来按顺序合并并行搜索的结果
+    pub fn append(&mut self, output: &[u8]) -> io::Result<()> {
+        if output.is_empty() {
+            return Ok(());
+        }
+        if self.context && self.printed {
+            let sep = self.paint(&self.colors.separator, "--");
+            writeln!(self.out, "{}", sep)?;
+        }
+        self.printed = true;
+        self.out.write_all(output)
+    }
+
+    /// 取回底层的输出
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    /// 当前文件的第一个事件之前输出 `begin`
+    fn begin(&mut self) -> io::Result<()> {
+        if !self.begun {
+            let begin = Object::new()
+                .string("type", "begin")
+                .string("path", &self.path)
+                .finish();
+            writeln!(self.out, "{}", begin)?;
+            self.begun = true;
+        }
+        Ok(())
+    }
+
+    /// `match` / `context` 事件共有的字段
+    fn event(&self, kind: &str, line_number: usize, byte_offset: usize, line: &str) -> Object {
+        Object::new()
+            .string("type", kind)
+            .string("path", &self.path)
+            .value("line_number", line_number)
+            .value("byte_offset", byte_offset)
+            .string("line", line)
+    }
+
+    fn separator(&mut self, line_number: usize) -> io::Result<()> {
+        let contiguous = self.last_line.is_some_and(|last| last + 1 == line_number);
+        if self.context && self.printed && !contiguous {
+            let sep = self.paint(&self.colors.separator, "--");
+            writeln!(self.out, "{}", sep)?;
+        }
+        self.last_line = Some(line_number);
+        self.printed = true;
+        Ok(())
+    }
+
+    fn paint(&self, style: &Style, text: &str) -> String {
+        if self.color {
+            style.paint(text).to_string()
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// 把行内的每个匹配用 `ms` 的样式标出来
+    fn highlight(&self, m: &Match) -> String {
+        if !self.color || m.spans.is_empty() {
+            return m.line.to_string();
+        }
+        let mut line = String::with_capacity(m.line.len() * 2);
+        let mut last = 0;
+        for &(start, end) in &m.spans {
+            line.push_str(&m.line[last..start]);
+            if end > start {
+                line.push_str(&self.paint(&self.colors.matched, &m.line[start..end]));
+            }
+            last = end;
+        }
+        line.push_str(&m.line[last..]);
+        line
+    }
+
+    fn prefix(
+        &self,
+        line_number: usize,
+        column: Option<usize>,
+        byte_offset: usize,
+        sep: char,
+    ) -> String {
+        let sep = self.paint(&self.colors.separator, &sep.to_string());
+        let mut prefix = String::new();
+        if self.with_filename {
+            prefix.push_str(&self.paint(&self.colors.filename, &self.path));
+            prefix.push_str(&sep);
+        }
+        if self.line_number {
+            prefix.push_str(&self.paint(&self.colors.line_number, &line_number.to_string()));
+            prefix.push_str(&sep);
+        }
+        if let (true, Some(column)) = (self.column, column) {
+            prefix.push_str(&self.paint(&self.colors.line_number, &column.to_string()));
+            prefix.push_str(&sep);
+        }
+        if self.byte_offset {
+            prefix.push_str(&self.paint(&self.colors.byte_offset, &byte_offset.to_string()));
+            prefix.push_str(&sep);
+        }
+        prefix
+    }
+}
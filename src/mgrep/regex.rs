@@ -0,0 +1,927 @@
+//! 自带的正则引擎：解析成语法树，编译成指令，再用 Pike VM 执行。
+//!
+//! 支持的语法：
+//! - 字面量、`.`、转义 `\d \w \s \D \W \S \n \t \r`
+//! - 字符类 `[a-z]`、`[^0-9]`、`[[:alpha:]]`
+//! - 锚点 `^ $ \b \B`
+//! - 分支 `a|b`
+//! - 重复 `* + ? {n} {n,} {n,m}`，以及非贪婪版本 `*? +? ?? {n,m}?`
+//! - 捕获组 `( )`、命名组 `(?P<name> )` / `(?<name> )`、非捕获组 `(?: )`
+//! - 行内标志 `(?i)` / `(?-i)` 打开或关闭忽略大小写，直到所在的组结束；`(?i: )` 只作用于组内
+//!
+//! Pike VM 同时推进所有线程，匹配时间与 `文本长度 * 指令数` 成正比，
+//! 不会出现回溯引擎那种指数级爆炸。
+
+use std::{error, fmt};
+
+use super::unicode;
+
+/// 单个重复次数的上限，防止 `a{100000}` 生成过大的程序
+const MAX_REPEAT: u32 = 1000;
+
+/// 编译后的指令数上限：嵌套的计数重复会相乘，例如 `((a{1000}){1000}){1000}`
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    UnclosedGroup,
+    UnopenedGroup,
+    UnclosedClass,
+    InvalidRange,
+    InvalidEscape,
+    InvalidGroupName,
+    InvalidFlag,
+    NothingToRepeat,
+    RepeatTooLarge,
+}
+
+/// 编译正则表达式时的错误，`pos` 为出错位置（字节偏移）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub pos: usize,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let msg = match self.kind {
+            ErrorKind::UnclosedGroup => "unclosed group",
+            ErrorKind::UnopenedGroup => "unopened group",
+            ErrorKind::UnclosedClass => "unclosed character class",
+            ErrorKind::InvalidRange => "invalid character class range",
+            ErrorKind::InvalidEscape => "invalid escape sequence",
+            ErrorKind::InvalidGroupName => "invalid group name",
+            ErrorKind::InvalidFlag => "unknown inline flag",
+            ErrorKind::NothingToRepeat => "repetition operator has nothing to repeat",
+            ErrorKind::RepeatTooLarge => "repetition count is too large",
+        };
+        write!(f, "regex parse error at {}: {}", self.pos, msg)
+    }
+}
+
+impl error::Error for Error {}
+
+#[derive(Debug, Clone)]
+struct CharClass {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl CharClass {
+    fn new(ranges: Vec<(char, char)>, negated: bool) -> CharClass {
+        CharClass { ranges, negated }
+    }
+
+    fn contains(&self, c: char, case_insensitive: bool) -> bool {
+        let hit = |c: char| self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+        let found = hit(c) || (case_insensitive && case_variants(c).any(hit));
+        found != self.negated
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    /// 第二个字段表示是否忽略大小写
+    Literal(char, bool),
+    Any,
+    Class(CharClass, bool),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+        /// 重复运算符的位置，程序太大时报告在这里
+        pos: usize,
+    },
+}
+
+struct Parser<'p> {
+    pattern: &'p str,
+    pos: usize,
+    groups: usize,
+    names: Vec<Option<String>>,
+    /// 当前位置是否忽略大小写，由行内标志改变
+    case_insensitive: bool,
+}
+
+impl<'p> Parser<'p> {
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            pos: self.pos,
+        }
+    }
+
+    fn parse_alternate(&mut self) -> Result<Node, Error> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.eat('|') {
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            let atom = self.parse_repeat(atom)?;
+            items.push(atom);
+        }
+        Ok(match items.len() {
+            0 => Node::Empty,
+            1 => items.pop().unwrap(),
+            _ => Node::Concat(items),
+        })
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        let start = self.pos;
+        let c = self.bump().unwrap();
+        let node = match c {
+            '.' => Node::Any,
+            '^' => Node::Assert(Assertion::Start),
+            '$' => Node::Assert(Assertion::End),
+            '[' => self.parse_class()?,
+            '(' => self.parse_group()?,
+            '\\' => self.parse_escape()?,
+            '*' | '+' | '?' => {
+                return Err(Error {
+                    kind: ErrorKind::NothingToRepeat,
+                    pos: start,
+                })
+            }
+            c => Node::Literal(c, self.case_insensitive),
+        };
+        Ok(node)
+    }
+
+    fn parse_group(&mut self) -> Result<Node, Error> {
+        let open = self.pos - 1;
+        // 组里的行内标志在组结束时失效
+        let outer = self.case_insensitive;
+        let index = if self.eat('?') {
+            if self.eat(':') {
+                None
+            } else if !matches!(self.peek(), Some('P' | '<')) {
+                self.case_insensitive = self.parse_flags()?;
+                if self.eat(')') {
+                    // `(?i)` 作用到所在的组结束
+                    return Ok(Node::Empty);
+                }
+                self.eat(':');
+                None
+            } else {
+                self.eat('P');
+                if !self.eat('<') {
+                    return Err(self.error(ErrorKind::InvalidGroupName));
+                }
+                let name_start = self.pos;
+                while let Some(c) = self.peek() {
+                    if c == '>' {
+                        break;
+                    }
+                    if !(c.is_alphanumeric() || c == '_') {
+                        return Err(self.error(ErrorKind::InvalidGroupName));
+                    }
+                    self.bump();
+                }
+                let name = self.pattern[name_start..self.pos].to_string();
+                if name.is_empty() || !self.eat('>') {
+                    return Err(self.error(ErrorKind::InvalidGroupName));
+                }
+                self.groups += 1;
+                self.names.push(Some(name));
+                Some(self.groups)
+            }
+        } else {
+            self.groups += 1;
+            self.names.push(None);
+            Some(self.groups)
+        };
+
+        let inner = self.parse_alternate()?;
+        self.case_insensitive = outer;
+        if !self.eat(')') {
+            return Err(Error {
+                kind: ErrorKind::UnclosedGroup,
+                pos: open,
+            });
+        }
+        Ok(Node::Group(Box::new(inner), index))
+    }
+
+    /// 解析 `(?i`、`(?-i` 之后到 `)` 或 `:` 之前的部分，返回之后是否忽略大小写
+    fn parse_flags(&mut self) -> Result<bool, Error> {
+        let negated = self.eat('-');
+        if !self.eat('i') || !matches!(self.peek(), Some(')' | ':')) {
+            return Err(self.error(ErrorKind::InvalidFlag));
+        }
+        Ok(!negated)
+    }
+
+    fn parse_escape(&mut self) -> Result<Node, Error> {
+        let c = self
+            .bump()
+            .ok_or_else(|| self.error(ErrorKind::InvalidEscape))?;
+        Ok(match c {
+            'b' => Node::Assert(Assertion::WordBoundary),
+            'B' => Node::Assert(Assertion::NotWordBoundary),
+            _ => match escape_class(c) {
+                Some(class) => Node::Class(class, self.case_insensitive),
+                None => Node::Literal(self.escape_literal(c)?, self.case_insensitive),
+            },
+        })
+    }
+
+    fn escape_literal(&self, c: char) -> Result<char, Error> {
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            c if !c.is_alphanumeric() => Ok(c),
+            _ => Err(Error {
+                kind: ErrorKind::InvalidEscape,
+                pos: self.pos - c.len_utf8() - 1,
+            }),
+        }
+    }
+
+    fn parse_class(&mut self) -> Result<Node, Error> {
+        let open = self.pos - 1;
+        let negated = self.eat('^');
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.bump() {
+                Some(c) => c,
+                None => {
+                    return Err(Error {
+                        kind: ErrorKind::UnclosedClass,
+                        pos: open,
+                    })
+                }
+            };
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = match c {
+                '[' if self.eat(':') => {
+                    ranges.extend(self.parse_posix_class()?);
+                    continue;
+                }
+                '\\' => {
+                    let e = self
+                        .bump()
+                        .ok_or_else(|| self.error(ErrorKind::UnclosedClass))?;
+                    if let Some(class) = escape_class(e) {
+                        // 字符类里的 \D \W \S 取补集后再合并
+                        ranges.extend(if class.negated {
+                            negate(&class.ranges)
+                        } else {
+                            class.ranges
+                        });
+                        continue;
+                    }
+                    self.escape_literal(e)?
+                }
+                c => c,
+            };
+
+            let is_range = self.peek() == Some('-')
+                && self.pattern[self.pos + 1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|n| n != ']');
+            if !is_range {
+                ranges.push((lo, lo));
+                continue;
+            }
+            self.bump();
+            let hi = match self.bump().unwrap() {
+                '\\' => {
+                    let e = self
+                        .bump()
+                        .ok_or_else(|| self.error(ErrorKind::UnclosedClass))?;
+                    self.escape_literal(e)?
+                }
+                c => c,
+            };
+            if hi < lo {
+                return Err(self.error(ErrorKind::InvalidRange));
+            }
+            ranges.push((lo, hi));
+        }
+        Ok(Node::Class(
+            CharClass::new(ranges, negated),
+            self.case_insensitive,
+        ))
+    }
+
+    fn parse_posix_class(&mut self) -> Result<Vec<(char, char)>, Error> {
+        let start = self.pos;
+        let end = match self.pattern[start..].find(":]") {
+            Some(end) => start + end,
+            None => return Err(self.error(ErrorKind::UnclosedClass)),
+        };
+        let ranges = match &self.pattern[start..end] {
+            "alpha" => vec![('a', 'z'), ('A', 'Z')],
+            "digit" => vec![('0', '9')],
+            "alnum" => vec![('a', 'z'), ('A', 'Z'), ('0', '9')],
+            "upper" => vec![('A', 'Z')],
+            "lower" => vec![('a', 'z')],
+            "space" => vec![(' ', ' '), ('\t', '\r')],
+            "xdigit" => vec![('0', '9'), ('a', 'f'), ('A', 'F')],
+            "word" => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            "punct" => vec![('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+            _ => return Err(self.error(ErrorKind::UnclosedClass)),
+        };
+        self.pos = end + 2;
+        Ok(ranges)
+    }
+
+    fn parse_repeat(&mut self, mut atom: Node) -> Result<Node, Error> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_counted()? {
+                    Some(range) => range,
+                    None => return Ok(atom),
+                },
+                _ => return Ok(atom),
+            };
+            if self.pos == start {
+                self.bump();
+            }
+            if matches!(atom, Node::Assert(_) | Node::Empty) {
+                return Err(Error {
+                    kind: ErrorKind::NothingToRepeat,
+                    pos: start,
+                });
+            }
+            let greedy = !self.eat('?');
+            atom = Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+                greedy,
+                pos: start,
+            };
+        }
+    }
+
+    /// 解析 `{n}`、`{n,}`、`{n,m}`；不是合法的计数重复时按字面量 `{` 处理
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let rest = &self.pattern[self.pos + 1..];
+        let close = match rest.find('}') {
+            Some(close) => close,
+            None => return Ok(None),
+        };
+        let body = &rest[..close];
+        let number = |s: &str| -> Option<u32> {
+            if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+                None
+            } else {
+                Some(s.parse().unwrap_or(u32::MAX))
+            }
+        };
+        let range = match body.split_once(',') {
+            None => number(body).map(|n| (n, Some(n))),
+            Some((lo, "")) => number(lo).map(|n| (n, None)),
+            Some((lo, hi)) => number(lo).zip(number(hi)).map(|(lo, hi)| (lo, Some(hi))),
+        };
+        let (min, max) = match range {
+            Some(range) => range,
+            None => return Ok(None),
+        };
+        if min > MAX_REPEAT || max.is_some_and(|max| max > MAX_REPEAT) {
+            return Err(self.error(ErrorKind::RepeatTooLarge));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error(ErrorKind::InvalidRange));
+        }
+        self.pos += close + 2;
+        Ok(Some((min, max)))
+    }
+}
+
+fn escape_class(c: char) -> Option<CharClass> {
+    let (ranges, negated) = match c {
+        'd' => (vec![('0', '9')], false),
+        'D' => (vec![('0', '9')], true),
+        'w' => (word_ranges(), false),
+        'W' => (word_ranges(), true),
+        's' => (space_ranges(), false),
+        'S' => (space_ranges(), true),
+        _ => return None,
+    };
+    Some(CharClass::new(ranges, negated))
+}
+
+fn word_ranges() -> Vec<(char, char)> {
+    vec![('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')]
+}
+
+fn space_ranges() -> Vec<(char, char)> {
+    vec![('\t', '\r'), (' ', ' ')]
+}
+
+fn negate(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut sorted = ranges.to_vec();
+    sorted.sort();
+    let mut out = Vec::new();
+    let mut next = '\0';
+    for (lo, hi) in sorted {
+        if lo > next {
+            out.push((next, prev_char(lo)));
+        }
+        if hi >= next {
+            next = match next_char(hi) {
+                Some(c) => c,
+                None => return out,
+            };
+        }
+    }
+    out.push((next, char::MAX));
+    out
+}
+
+fn prev_char(c: char) -> char {
+    match c {
+        '\u{E000}' => '\u{D7FF}',
+        c => char::from_u32(c as u32 - 1).unwrap(),
+    }
+}
+
+fn next_char(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        c => char::from_u32(c as u32 + 1),
+    }
+}
+
+fn single(mut it: impl Iterator<Item = char>) -> Option<char> {
+    let c = it.next()?;
+    it.next().is_none().then_some(c)
+}
+
+/// 忽略大小写时字符类要额外尝试的写法：折叠结果以及单字符的大小写形式
+fn case_variants(c: char) -> impl Iterator<Item = char> {
+    let folded = Some(unicode::simple_fold(c));
+    let lower = single(c.to_lowercase());
+    let upper = single(c.to_uppercase());
+    [folded, lower, upper]
+        .into_iter()
+        .flatten()
+        .filter(move |&v| v != c)
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    /// 第二个字段表示是否忽略大小写
+    Char(char, bool),
+    Any,
+    Class(CharClass, bool),
+    Assert(Assertion),
+    Split(usize, usize),
+    Jmp(usize),
+    Save(usize),
+    Match,
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+}
+
+impl Compiler {
+    fn emit(&mut self, inst: Inst) -> usize {
+        self.prog.push(inst);
+        self.prog.len() - 1
+    }
+
+    fn patch_split(&mut self, at: usize, first: usize, second: usize) {
+        self.prog[at] = Inst::Split(first, second);
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), Error> {
+        match node {
+            Node::Empty => {}
+            Node::Literal(c, fold) => {
+                self.emit(Inst::Char(*c, *fold));
+            }
+            Node::Any => {
+                self.emit(Inst::Any);
+            }
+            Node::Class(class, fold) => {
+                self.emit(Inst::Class(class.clone(), *fold));
+            }
+            Node::Assert(a) => {
+                self.emit(Inst::Assert(*a));
+            }
+            Node::Group(inner, index) => match index {
+                Some(index) => {
+                    self.emit(Inst::Save(index * 2));
+                    self.compile(inner)?;
+                    self.emit(Inst::Save(index * 2 + 1));
+                }
+                None => self.compile(inner)?,
+            },
+            Node::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.compile(branch)?;
+                    } else {
+                        let split = self.emit(Inst::Split(0, 0));
+                        self.compile(branch)?;
+                        jumps.push(self.emit(Inst::Jmp(0)));
+                        let next = self.prog.len();
+                        self.patch_split(split, split + 1, next);
+                    }
+                }
+                let end = self.prog.len();
+                for jump in jumps {
+                    self.prog[jump] = Inst::Jmp(end);
+                }
+            }
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+                pos,
+            } => {
+                for _ in 0..*min {
+                    self.compile_bounded(node, *pos)?;
+                }
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0));
+                        self.compile_bounded(node, *pos)?;
+                        self.emit(Inst::Jmp(split));
+                        let end = self.prog.len();
+                        self.split_to(split, split + 1, end, *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0)));
+                            self.compile_bounded(node, *pos)?;
+                        }
+                        let end = self.prog.len();
+                        for split in splits {
+                            self.split_to(split, split + 1, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// 编译重复的一份；每份都检查一次总指令数，嵌套的重复在超出上限后马上停下
+    fn compile_bounded(&mut self, node: &Node, pos: usize) -> Result<(), Error> {
+        self.compile(node)?;
+        if self.prog.len() > MAX_PROGRAM {
+            return Err(Error {
+                kind: ErrorKind::RepeatTooLarge,
+                pos,
+            });
+        }
+        Ok(())
+    }
+
+    fn split_to(&mut self, at: usize, body: usize, exit: usize, greedy: bool) {
+        if greedy {
+            self.patch_split(at, body, exit);
+        } else {
+            self.patch_split(at, exit, body);
+        }
+    }
+}
+
+/// 编译好的正则表达式
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    prog: Vec<Inst>,
+    names: Vec<Option<String>>,
+}
+
+/// 一次匹配中各个捕获组的位置，第 0 组是整个匹配
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'t> {
+    text: &'t str,
+    slots: Vec<Option<usize>>,
+    names: Vec<Option<String>>,
+}
+
+impl<'t> Captures<'t> {
+    /// 第 `i` 组的字节区间
+    pub fn pos(&self, i: usize) -> Option<(usize, usize)> {
+        match (self.slots.get(i * 2)?, self.slots.get(i * 2 + 1)?) {
+            (Some(s), Some(e)) => Some((*s, *e)),
+            _ => None,
+        }
+    }
+
+    /// 第 `i` 组匹配到的文本
+    pub fn get(&self, i: usize) -> Option<&'t str> {
+        self.pos(i).map(|(s, e)| &self.text[s..e])
+    }
+
+    /// 按组名取匹配到的文本
+    pub fn name(&self, name: &str) -> Option<&'t str> {
+        let i = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(i + 1)
+    }
+
+    /// 捕获组个数（包括第 0 组）
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+type Slots = Vec<Option<usize>>;
+
+/// Pike VM 的线程列表：按优先级排列的 pc，以及每个 pc 对应的捕获位置
+struct Threads {
+    dense: Vec<usize>,
+    slots: Vec<Option<Slots>>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            dense: Vec::with_capacity(len),
+            slots: vec![None; len],
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        self.slots[pc].is_some()
+    }
+
+    fn clear(&mut self) {
+        for pc in self.dense.drain(..) {
+            self.slots[pc] = None;
+        }
+    }
+}
+
+impl Regex {
+    /// 编译正则表达式
+    pub fn new(pattern: &str) -> Result<Regex, Error> {
+        Regex::build(pattern, false)
+    }
+
+    /// 编译忽略大小写的正则表达式，等同于在开头加上 `(?i)`
+    pub fn new_case_insensitive(pattern: &str) -> Result<Regex, Error> {
+        Regex::build(pattern, true)
+    }
+
+    fn build(pattern: &str, case_insensitive: bool) -> Result<Regex, Error> {
+        let mut parser = Parser {
+            pattern,
+            pos: 0,
+            groups: 0,
+            names: Vec::new(),
+            case_insensitive,
+        };
+        let ast = parser.parse_alternate()?;
+        if parser.pos < pattern.len() {
+            return Err(parser.error(ErrorKind::UnopenedGroup));
+        }
+
+        let mut compiler = Compiler { prog: Vec::new() };
+        compiler.emit(Inst::Save(0));
+        compiler.compile(&ast)?;
+        compiler.emit(Inst::Save(1));
+        compiler.emit(Inst::Match);
+
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            prog: compiler.prog,
+            names: parser.names,
+        })
+    }
+
+    /// 原始的正则表达式文本
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// 捕获组个数（包括第 0 组）
+    pub fn captures_len(&self) -> usize {
+        self.names.len() + 1
+    }
+
+    /// 命名捕获组的序号
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        let i = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        Some(i + 1)
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+
+    /// 第一个匹配的字节区间
+    pub fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.find_at(text, 0)
+    }
+
+    /// 从 `start` 开始查找第一个匹配；`^`、`\b` 等断言仍然参考 `start` 之前的文本
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        let slots = self.exec(text, start)?;
+        Some((slots[0]?, slots[1]?))
+    }
+
+    pub fn captures<'t>(&self, text: &'t str) -> Option<Captures<'t>> {
+        self.captures_at(text, 0)
+    }
+
+    pub fn captures_at<'t>(&self, text: &'t str, start: usize) -> Option<Captures<'t>> {
+        let slots = self.exec(text, start)?;
+        Some(Captures {
+            text,
+            slots,
+            names: self.names.clone(),
+        })
+    }
+
+    fn exec(&self, text: &str, start: usize) -> Option<Slots> {
+        let len = self.prog.len();
+        let mut clist = Threads::new(len);
+        let mut nlist = Threads::new(len);
+        let mut matched: Option<Slots> = None;
+        let empty: Slots = vec![None; self.captures_len() * 2];
+
+        let mut pos = start;
+        loop {
+            let c = text[pos..].chars().next();
+            if matched.is_none() {
+                self.add_thread(&mut clist, 0, pos, text, empty.clone());
+            }
+            if clist.dense.is_empty() {
+                break;
+            }
+
+            for i in 0..clist.dense.len() {
+                let pc = clist.dense[i];
+                let slots = clist.slots[pc].take().unwrap();
+                match &self.prog[pc] {
+                    Inst::Match => {
+                        matched = Some(slots);
+                        // 优先级更低的线程不再需要
+                        break;
+                    }
+                    inst => {
+                        if let Some(c) = c {
+                            if self.step(inst, c) {
+                                self.add_thread(
+                                    &mut nlist,
+                                    pc + 1,
+                                    pos + c.len_utf8(),
+                                    text,
+                                    slots,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            clist.clear();
+            std::mem::swap(&mut clist, &mut nlist);
+
+            match c {
+                Some(c) => pos += c.len_utf8(),
+                None => break,
+            }
+        }
+        matched
+    }
+
+    fn step(&self, inst: &Inst, c: char) -> bool {
+        match inst {
+            Inst::Char(want, fold) => *want == c || (*fold && unicode::eq_fold(*want, c)),
+            Inst::Any => c != '\n',
+            Inst::Class(class, fold) => class.contains(c, *fold),
+            _ => false,
+        }
+    }
+
+    /// 把 `pc` 以及从它出发不消耗字符能到达的指令加入线程列表。
+    /// 用显式的栈代替递归，很长的 Split/Save 链也不会栈溢出；
+    /// 栈顶是下一个要走的分支，所以线程的优先级和深度优先递归时一样
+    fn add_thread(&self, list: &mut Threads, pc: usize, pos: usize, text: &str, slots: Slots) {
+        let mut stack = vec![(pc, slots)];
+        while let Some((mut pc, mut slots)) = stack.pop() {
+            loop {
+                if list.contains(pc) {
+                    break;
+                }
+                match &self.prog[pc] {
+                    Inst::Jmp(to) => pc = *to,
+                    Inst::Split(first, second) => {
+                        // 占位，防止空循环无限展开
+                        list.slots[pc] = Some(Vec::new());
+                        list.dense.push(pc);
+                        stack.push((*second, slots.clone()));
+                        pc = *first;
+                    }
+                    Inst::Save(slot) => {
+                        list.slots[pc] = Some(Vec::new());
+                        list.dense.push(pc);
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Assert(assertion) => {
+                        list.slots[pc] = Some(Vec::new());
+                        list.dense.push(pc);
+                        if !assert(*assertion, text, pos) {
+                            break;
+                        }
+                        pc += 1;
+                    }
+                    _ => {
+                        list.slots[pc] = Some(slots);
+                        list.dense.push(pc);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn assert(assertion: Assertion, text: &str, pos: usize) -> bool {
+    match assertion {
+        Assertion::Start => pos == 0,
+        Assertion::End => pos == text.len(),
+        Assertion::WordBoundary | Assertion::NotWordBoundary => {
+            let before = text[..pos]
+                .chars()
+                .next_back()
+                .is_some_and(unicode::is_word_char);
+            let after = text[pos..]
+                .chars()
+                .next()
+                .is_some_and(unicode::is_word_char);
+            (before != after) == matches!(assertion, Assertion::WordBoundary)
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! `--replace` 的替换模板。
+//!
+//! 模板里 `$0`、`$1` … 引用捕获组（`$0` 是整个匹配），`$name` 或 `${name}` 引用命名捕获组，
+//! `$$` 表示 `$` 本身。不存在或者没有参与匹配的组替换成空字符串。
+//! 不是正则表达式模式时只有 `$0`。
+
+use super::matcher::Matcher;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Group(usize),
+    Named(String),
+}
+
+/// 解析好的替换模板
+///
+/// ```
+/// use learning_rust::mgrep::{regex::Regex, replace::Replacer};
+///
+/// let re = Regex::new(r"(?P<key>\w+)=(\d+)").unwrap();
+/// let replacer = Replacer::new("$2=${key}");
+/// let (line, spans) = replacer.replace(&re, "a=1, bb=22").unwrap();
+/// assert_eq!("1=a, 22=bb", line);
+/// assert_eq!(vec![(0, 3), (5, 10)], spans);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacer {
+    parts: Vec<Part>,
+}
+
+impl Replacer {
+    pub fn new(template: &str) -> Replacer {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(i) = rest.find('$') {
+            text.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            let (reference, len) = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => (&braced[..end], end + 2),
+                    None => ("", 0),
+                }
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            };
+            if reference.is_empty() {
+                // `$$` 以及后面不是组名的 `$` 都按字面输出
+                text.push('$');
+                if rest.starts_with('$') {
+                    rest = &rest[1..];
+                }
+                continue;
+            }
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(match reference.parse() {
+                Ok(index) => Part::Group(index),
+                Err(_) => Part::Named(reference.to_string()),
+            });
+            rest = &rest[len..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Replacer { parts }
+    }
+
+    /// 替换 `line` 里所有不重叠的匹配
+    ///
+    /// 没有匹配时返回 `None`，否则返回新的一行以及每段替换结果在新行里的字节区间。
+    pub fn replace(
+        &self,
+        matcher: &dyn Matcher,
+        line: &str,
+    ) -> Option<(String, Vec<(usize, usize)>)> {
+        let mut replaced = String::with_capacity(line.len());
+        let mut spans = Vec::new();
+        let mut last = 0;
+        let mut start = 0;
+        while start <= line.len() {
+            let groups = match matcher.captures_at(line, start) {
+                Some(groups) => groups,
+                None => break,
+            };
+            let (s, e) = match groups.first() {
+                Some(&Some(span)) => span,
+                _ => break,
+            };
+            replaced.push_str(&line[last..s]);
+            let begin = replaced.len();
+            for part in &self.parts {
+                let group = match part {
+                    Part::Text(text) => {
+                        replaced.push_str(text);
+                        continue;
+                    }
+                    Part::Group(i) => groups.get(*i).copied().flatten(),
+                    Part::Named(name) => matcher
+                        .group_index(name)
+                        .and_then(|i| groups.get(i).copied().flatten()),
+                };
+                if let Some((gs, ge)) = group {
+                    replaced.push_str(&line[gs..ge]);
+                }
+            }
+            spans.push((begin, replaced.len()));
+            last = e;
+            // 和 `Matcher::find_iter` 一样，空匹配之后跳过一个字符
+            start = if e > s {
+                e
+            } else {
+                match line[e..].chars().next() {
+                    Some(c) => e + c.len_utf8(),
+                    None => break,
+                }
+            };
+        }
+        if spans.is_empty() {
+            return None;
+        }
+        replaced.push_str(&line[last..]);
+        Some((replaced, spans))
+    }
+}
@@ -0,0 +1,445 @@
+//! 基于 `BufRead` 的流式搜索。
+//!
+//! 每次只读一行，内存占用只和最长的一行以及 `-B` 的行数有关，
+//! 所以可以搜索几个 GB 的日志，也可以搜索管道里的 stdin。
+//!
+//! `Searcher` 只负责找出选中行和上下文，结果交给实现了 `Sink` 的对象处理，
+//! `minigrep` 用的 `Printer` 就是其中一种，其他程序可以自己实现 `Sink` 来复用搜索。
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+use super::{
+    gzip::{self, GzDecoder},
+    matcher::Matcher,
+    printer::Printer,
+    replace::Replacer,
+    walk, Config, Match, STDIN,
+};
+
+/// 一个输入源的搜索统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// 读过的行数
+    pub lines: usize,
+    /// 选中的行数（`-v` 时是不匹配的行数）
+    pub selected: usize,
+    /// 因为不是合法 UTF-8 而跳过的行数
+    pub invalid_utf8: usize,
+    /// 开头出现 NUL 字节，按二进制文件跳过
+    pub binary: bool,
+}
+
+/// 所有输入源的汇总统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// 搜索过的文件数，不包括打开或者读取失败的
+    pub files: usize,
+    /// 有选中行的文件数
+    pub files_matched: usize,
+    /// 选中的总行数
+    pub selected: usize,
+    /// 出错的文件数
+    pub errors: usize,
+}
+
+impl Summary {
+    pub fn add(&mut self, stats: &Stats) {
+        self.files += 1;
+        if stats.selected > 0 {
+            self.files_matched += 1;
+        }
+        self.selected += stats.selected;
+    }
+}
+
+/// 每读这么多行调用一次 `Sink::progress`
+pub const PROGRESS_LINES: usize = 4096;
+
+/// 接收搜索结果
+///
+/// 除了 `matched` 都有默认实现。返回 `Ok(false)` 表示不再需要结果，搜索会停在当前行，
+/// 返回错误时搜索以这个错误结束。
+pub trait Sink {
+    /// 选中的一行
+    fn matched(&mut self, m: &Match) -> io::Result<bool>;
+
+    /// `-A` / `-B` 的上下文行
+    fn context(
+        &mut self,
+        _line_number: usize,
+        _byte_offset: usize,
+        _line: &str,
+    ) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// 跳过了一行不是合法 UTF-8 的行，`valid_up_to` 是这一行里出错的字节位置
+    fn invalid_utf8(&mut self, _line_number: usize, _valid_up_to: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// 每读 `PROGRESS_LINES` 行调用一次，`stats` 是到目前为止的统计
+    fn progress(&mut self, _stats: &Stats) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for &mut S {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        (**self).matched(m)
+    }
+
+    fn context(&mut self, line_number: usize, byte_offset: usize, line: &str) -> io::Result<bool> {
+        (**self).context(line_number, byte_offset, line)
+    }
+
+    fn invalid_utf8(&mut self, line_number: usize, valid_up_to: usize) -> io::Result<()> {
+        (**self).invalid_utf8(line_number, valid_up_to)
+    }
+
+    fn progress(&mut self, stats: &Stats) -> io::Result<bool> {
+        (**self).progress(stats)
+    }
+}
+
+impl<W: Write> Sink for Printer<W> {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        Printer::matched(self, m)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, line_number: usize, byte_offset: usize, line: &str) -> io::Result<bool> {
+        Printer::context(self, line_number, byte_offset, line)?;
+        Ok(true)
+    }
+
+    fn invalid_utf8(&mut self, line_number: usize, valid_up_to: usize) -> io::Result<()> {
+        Printer::invalid_utf8(self, line_number, valid_up_to)
+    }
+}
+
+/// `--fuzzy` 时先攒起来、最后按距离排序输出的一行
+struct Ranked {
+    distance: usize,
+    line_number: usize,
+    byte_offset: usize,
+    line: String,
+    spans: Vec<(usize, usize)>,
+}
+
+/// 逐行搜索一个输入源，把结果交给 `Sink`
+///
+/// 不依赖 `Config`，可以单独使用：
+///
+/// ```
+/// use learning_rust::mgrep::{matcher::Literal, searcher::{Searcher, Sink}, Match};
+///
+/// struct Lines(Vec<usize>);
+///
+/// impl Sink for Lines {
+///     fn matched(&mut self, m: &Match) -> std::io::Result<bool> {
+///         self.0.push(m.line_number);
+///         Ok(true)
+///     }
+/// }
+///
+/// let mut lines = Lines(Vec::new());
+/// let stats = Searcher::new()
+///     .max_count(Some(2))
+///     .search_reader(&Literal::new("a", false), &b"a\nb\na\na\n"[..], &mut lines)
+///     .unwrap();
+/// assert_eq!(vec![1, 3], lines.0);
+/// assert_eq!(2, stats.selected);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    invert_match: bool,
+    before_context: usize,
+    after_context: usize,
+    max_count: Option<usize>,
+    count_only: bool,
+    replacer: Option<Replacer>,
+    sort_by_distance: bool,
+}
+
+impl Searcher {
+    pub fn new() -> Searcher {
+        Searcher::default()
+    }
+
+    /// 按 `minigrep` 的命令行参数设置
+    pub fn from_config(config: &Config) -> Searcher {
+        // `-c` / `-l` / `-L` / `-q` 不输出行内容
+        let count_only =
+            config.count || config.files_with_matches || config.files_without_match || config.quiet;
+        // `-l` / `-L` / `-q` 只关心有没有匹配，找到第一个就可以停下
+        let first_only = config.files_with_matches || config.files_without_match || config.quiet;
+        let max_count = if first_only {
+            Some(config.max_count.map_or(1, |max| max.min(1)))
+        } else {
+            config.max_count
+        };
+        Searcher {
+            invert_match: config.invert_match,
+            before_context: config.before_context,
+            after_context: config.after_context,
+            max_count,
+            count_only,
+            replacer: config.replace.as_deref().map(Replacer::new),
+            sort_by_distance: config.fuzzy.is_some(),
+        }
+    }
+
+    /// 选中不匹配的行
+    pub fn invert_match(mut self, yes: bool) -> Searcher {
+        self.invert_match = yes;
+        self
+    }
+
+    /// 每个选中行之前、之后输出的上下文行数
+    pub fn context(mut self, before: usize, after: usize) -> Searcher {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    /// 最多选中多少行
+    pub fn max_count(mut self, max_count: Option<usize>) -> Searcher {
+        self.max_count = max_count;
+        self
+    }
+
+    /// 只统计选中的行数，不把行交给 `Sink`
+    pub fn count_only(mut self, yes: bool) -> Searcher {
+        self.count_only = yes;
+        self
+    }
+
+    /// 交给 `Sink` 的是替换以后的行，`spans` 指向替换进去的文本
+    pub fn replace(mut self, replacer: Option<Replacer>) -> Searcher {
+        self.replacer = replacer;
+        self
+    }
+
+    /// 一个输入源里的结果按 `Matcher::distance` 从小到大交给 `Sink`，距离相同的保持原来的顺序，
+    /// 这时没有上下文，`-v` 时不起作用
+    pub fn sort_by_distance(mut self, yes: bool) -> Searcher {
+        self.sort_by_distance = yes;
+        self
+    }
+
+    /// 搜索一个文件，`-` 表示 stdin；开头是 gzip 的魔数时搜索解压以后的内容
+    pub fn search_path<S: Sink>(
+        &self,
+        matcher: &dyn Matcher,
+        path: &Path,
+        sink: S,
+    ) -> io::Result<Stats> {
+        if path == Path::new(STDIN) {
+            self.search_input(matcher, io::stdin().lock(), sink)
+        } else {
+            let reader = BufReader::with_capacity(64 * 1024, File::open(path)?);
+            self.search_input(matcher, reader, sink)
+        }
+    }
+
+    fn search_input<R: BufRead, S: Sink>(
+        &self,
+        matcher: &dyn Matcher,
+        mut reader: R,
+        sink: S,
+    ) -> io::Result<Stats> {
+        if gzip::is_gzip(reader.fill_buf()?) {
+            let reader = BufReader::with_capacity(64 * 1024, GzDecoder::new(reader));
+            self.search_reader(matcher, reader, sink)
+        } else {
+            self.search_reader(matcher, reader, sink)
+        }
+    }
+
+    /// 从 `reader` 逐行搜索
+    ///
+    /// 每次只读一行；开头 8KB 里有 NUL 字节时按二进制文件跳过，
+    /// 不是合法 UTF-8 的行交给 `Sink::invalid_utf8` 以后跳过，不会中断搜索。
+    pub fn search_reader<R: BufRead, S: Sink>(
+        &self,
+        matcher: &dyn Matcher,
+        mut reader: R,
+        mut sink: S,
+    ) -> io::Result<Stats> {
+        let mut stats = Stats::default();
+        if walk::is_binary(reader.fill_buf()?) {
+            stats.binary = true;
+            return Ok(stats);
+        }
+
+        let quiet = self.count_only;
+        let max_count = self.max_count.unwrap_or(usize::MAX);
+        let ranked = self.sort_by_distance && !self.invert_match && !quiet;
+        let mut ranking = Vec::new();
+        let (before_context, after_context) = if ranked {
+            (0, 0)
+        } else {
+            (self.before_context, self.after_context)
+        };
+        let mut before: VecDeque<(usize, usize, String)> = VecDeque::with_capacity(before_context);
+        let mut after_left: usize = 0;
+        let mut buf = Vec::new();
+        let mut byte_offset = 0;
+
+        // `-m` 时达到上限以后只再输出剩下的后文
+        while stats.selected < max_count || (!quiet && after_left > 0) {
+            buf.clear();
+            let read = reader.read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                break;
+            }
+            stats.lines += 1;
+            let line_number = stats.lines;
+            let offset = byte_offset;
+            byte_offset += read;
+            if line_number % PROGRESS_LINES == 0 && !sink.progress(&stats)? {
+                break;
+            }
+
+            let line = match std::str::from_utf8(trim_newline(&buf)) {
+                Ok(line) => line,
+                Err(e) => {
+                    stats.invalid_utf8 += 1;
+                    sink.invalid_utf8(line_number, e.valid_up_to())?;
+                    // 跳过的这一行也占上下文窗口里的一行
+                    after_left = after_left.saturating_sub(1);
+                    continue;
+                }
+            };
+
+            let mut replaced = None;
+            let spans = if self.invert_match {
+                Vec::new()
+            } else if let Some(replacer) = &self.replacer {
+                replacer
+                    .replace(matcher, line)
+                    .map_or_else(Vec::new, |(line, spans)| {
+                        replaced = Some(line);
+                        spans
+                    })
+            } else {
+                matcher.find_iter(line)
+            };
+            let selected = if self.invert_match {
+                !matcher.is_match(line)
+            } else {
+                !spans.is_empty()
+            };
+
+            let more = if selected && stats.selected < max_count {
+                stats.selected += 1;
+                if quiet {
+                    continue;
+                }
+                if ranked {
+                    ranking.push(Ranked {
+                        distance: matcher.distance(line).unwrap_or(0),
+                        line_number,
+                        byte_offset: offset,
+                        line: replaced.unwrap_or_else(|| line.to_string()),
+                        spans,
+                    });
+                    continue;
+                }
+                let mut more = true;
+                // 中间隔着不合法的行时，前面攒的行可能已经不在窗口里了
+                for (n, o, text) in before.drain(..) {
+                    if n + before_context >= line_number {
+                        more = more && sink.context(n, o, &text)?;
+                    }
+                }
+                after_left = after_context;
+                more && sink.matched(&Match {
+                    line_number,
+                    byte_offset: offset,
+                    line: replaced.as_deref().unwrap_or(line),
+                    spans,
+                })?
+            } else if quiet {
+                continue;
+            } else if after_left > 0 {
+                after_left -= 1;
+                sink.context(line_number, offset, line)?
+            } else {
+                if before_context > 0 {
+                    // 复用被挤出去的 String，避免每行都重新分配
+                    let mut text = if before.len() == before_context {
+                        before
+                            .pop_front()
+                            .map(|(_, _, text)| text)
+                            .unwrap_or_default()
+                    } else {
+                        String::new()
+                    };
+                    text.clear();
+                    text.push_str(line);
+                    before.push_back((line_number, offset, text));
+                }
+                true
+            };
+            if !more {
+                break;
+            }
+        }
+
+        ranking.sort_by_key(|r| r.distance);
+        for r in ranking {
+            let m = Match {
+                line_number: r.line_number,
+                byte_offset: r.byte_offset,
+                line: &r.line,
+                spans: r.spans,
+            };
+            if !sink.matched(&m)? {
+                break;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// 按 `config` 搜索 `reader`，输出到 `printer`，包括 `-c` / `-l` / `-L` 在最后输出的内容
+pub fn search_reader<R: BufRead, W: Write>(
+    config: &Config,
+    matcher: &dyn Matcher,
+    reader: R,
+    printer: &mut Printer<W>,
+) -> io::Result<Stats> {
+    let stats = Searcher::from_config(config).search_reader(matcher, reader, &mut *printer)?;
+    print_totals(config, &stats, printer)?;
+    Ok(stats)
+}
+
+/// 一个输入源搜索完以后，按 `-c` / `-l` / `-L` 输出行数或者文件名；二进制文件什么都不输出
+pub fn print_totals<W: Write>(
+    config: &Config,
+    stats: &Stats,
+    printer: &mut Printer<W>,
+) -> io::Result<()> {
+    if stats.binary || config.quiet {
+        // `-q` 什么都不输出
+    } else if config.count {
+        printer.count(stats.selected)?;
+    } else if config.files_with_matches && stats.selected > 0
+        || config.files_without_match && stats.selected == 0
+    {
+        printer.path()?;
+    }
+    Ok(())
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
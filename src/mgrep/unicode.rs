@@ -0,0 +1,93 @@
+//! 忽略大小写和单词边界用到的 Unicode 规则。
+//!
+//! `simple_fold` 实现 Unicode 的简单大小写折叠（CaseFolding.txt 里的 C 和 S 项）：
+//! 每个字符只折叠成一个字符，所以匹配区间的字节偏移不会错位，也不需要为每一行分配新的字符串。
+//! 按照这个规则：
+//! - `ẞ` 和 `ß` 相同，但 `ß` 不等于 `ss`（那是完整折叠）
+//! - `İ`（带点大写 I）只等于自己，不会变成 `i` 加一个组合点
+//! - `ς`、`σ`、`Σ` 相同，`ſ` 和 `s` 相同
+
+/// 简单大小写折叠后和 `char::to_lowercase` 结果不同的字符
+const FOLD_EXCEPTIONS: &[(char, char)] = &[
+    ('\u{00B5}', '\u{03BC}'), // µ -> μ
+    ('\u{017F}', 's'),        // ſ -> s
+    ('\u{0345}', '\u{03B9}'), // 组合用 ypogegrammeni -> ι
+    ('\u{03C2}', '\u{03C3}'), // ς -> σ
+    ('\u{03D0}', '\u{03B2}'), // ϐ -> β
+    ('\u{03D1}', '\u{03B8}'), // ϑ -> θ
+    ('\u{03D5}', '\u{03C6}'), // ϕ -> φ
+    ('\u{03D6}', '\u{03C0}'), // ϖ -> π
+    ('\u{03F0}', '\u{03BA}'), // ϰ -> κ
+    ('\u{03F1}', '\u{03C1}'), // ϱ -> ρ
+    ('\u{03F5}', '\u{03B5}'), // ϵ -> ε
+    ('\u{1C80}', '\u{0432}'), // ᲀ -> в
+    ('\u{1C81}', '\u{0434}'), // ᲁ -> д
+    ('\u{1C82}', '\u{043E}'), // ᲂ -> о
+    ('\u{1C83}', '\u{0441}'), // ᲃ -> с
+    ('\u{1C84}', '\u{0442}'), // ᲄ -> т
+    ('\u{1C85}', '\u{0442}'), // ᲅ -> т
+    ('\u{1C86}', '\u{044A}'), // ᲆ -> ъ
+    ('\u{1C87}', '\u{0463}'), // ᲇ -> ѣ
+    ('\u{1C88}', '\u{A64B}'), // ᲈ -> ꙋ
+    ('\u{1E9B}', '\u{1E61}'), // ẛ -> ṡ
+    ('\u{1FBE}', '\u{03B9}'), // ι -> ι
+    ('\u{FB05}', '\u{FB06}'), // ﬅ -> ﬆ
+];
+
+/// Unicode 简单大小写折叠
+pub fn simple_fold(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    if let Ok(i) = FOLD_EXCEPTIONS.binary_search_by_key(&c, |&(from, _)| from) {
+        return FOLD_EXCEPTIONS[i].1;
+    }
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(l), None) => l,
+        // 小写形式不止一个字符（例如 `İ`）时，简单折叠保持不变
+        _ => c,
+    }
+}
+
+/// 忽略大小写时两个字符是否相同
+pub fn eq_fold(a: char, b: char) -> bool {
+    a == b || simple_fold(a) == simple_fold(b)
+}
+
+/// 是否是组成单词的字符：字母、数字、组合记号、连接标点（如 `_`）以及 ZWJ/ZWNJ
+pub fn is_word_char(c: char) -> bool {
+    if c.is_ascii() {
+        return c.is_ascii_alphanumeric() || c == '_';
+    }
+    c.is_alphanumeric() || is_mark(c) || is_connector(c) || c == '\u{200C}' || c == '\u{200D}'
+}
+
+/// 常见的组合记号区块，`char::is_alphabetic` 不包含其中的大部分
+fn is_mark(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{0483}'..='\u{0489}'
+        | '\u{0591}'..='\u{05BD}'
+        | '\u{0610}'..='\u{061A}'
+        | '\u{064B}'..='\u{065F}'
+        | '\u{0900}'..='\u{0903}'
+        | '\u{093A}'..='\u{094F}'
+        | '\u{0E31}'..='\u{0E3A}'
+        | '\u{0E47}'..='\u{0E4E}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{3099}'..='\u{309A}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{FE20}'..='\u{FE2F}')
+}
+
+/// Unicode 的连接标点（Pc）
+fn is_connector(c: char) -> bool {
+    matches!(
+        c,
+        '_' | '\u{203F}' | '\u{2040}' | '\u{2054}' | '\u{FE33}' | '\u{FE34}' | '\u{FE4D}'
+            ..='\u{FE4F}' | '\u{FF3F}'
+    )
+}
@@ -0,0 +1,288 @@
+//! 递归遍历目录，遵守 `.gitignore` / `.ignore` 里的忽略规则。
+//!
+//! 每个目录下的忽略文件只作用于该目录及其子目录，越深的规则优先级越高；
+//! 同一个文件里后出现的规则覆盖先出现的规则，`!` 开头的规则表示重新包含。
+//! 忽略规则之外的过滤（文件类型、大小、隐藏文件、符号链接）由 `filter` 模块决定。
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use super::{filter::Filter, glob::Glob};
+
+/// 会被读取的忽略文件名
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+#[derive(Debug)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+    dir_only: bool,
+    /// 规则里带 `/` 时相对忽略文件所在目录匹配，否则只匹配文件名
+    anchored: bool,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, line) = match line.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        if line.is_empty() {
+            return None;
+        }
+        Some(Rule {
+            glob: Glob::new(line),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            self.glob.is_match(relative)
+        } else {
+            self.glob.is_match(name)
+        }
+    }
+}
+
+/// 某个目录下的忽略规则，`parent` 指向上层目录的规则
+#[derive(Debug)]
+struct Ignore {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+    parent: Option<Rc<Ignore>>,
+}
+
+impl Ignore {
+    fn load(dir: &Path, parent: Option<Rc<Ignore>>) -> Rc<Ignore> {
+        let mut rules = Vec::new();
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                rules.extend(contents.lines().filter_map(Rule::parse));
+            }
+        }
+        if rules.is_empty() {
+            if let Some(parent) = parent {
+                return parent;
+            }
+        }
+        Rc::new(Ignore {
+            dir: dir.to_path_buf(),
+            rules,
+            parent,
+        })
+    }
+
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return false,
+        };
+        let mut current = Some(self);
+        while let Some(ignore) = current {
+            if let Ok(relative) = path.strip_prefix(&ignore.dir) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                if let Some(rule) = ignore
+                    .rules
+                    .iter()
+                    .rev()
+                    .find(|rule| rule.matches(&relative, &name, is_dir))
+                {
+                    return !rule.negated;
+                }
+            }
+            current = ignore.parent.as_deref();
+        }
+        false
+    }
+}
+
+/// 跟随符号链接时记录从起点到当前目录经过的真实路径，用来发现循环
+#[derive(Debug)]
+struct Ancestor {
+    dir: PathBuf,
+    parent: Option<Rc<Ancestor>>,
+}
+
+impl Ancestor {
+    fn contains(&self, dir: &Path) -> bool {
+        let mut current = Some(self);
+        while let Some(ancestor) = current {
+            if ancestor.dir == dir {
+                return true;
+            }
+            current = ancestor.parent.as_deref();
+        }
+        false
+    }
+}
+
+/// 深度优先遍历目录下的所有文件，同一目录内按文件名排序
+///
+/// # Example
+/// ```
+/// use learning_rust::mgrep::{filter::Filter, walk::Walk};
+///
+/// for path in Walk::new("src/mgrep").with_filter(Filter::new().file_type("rust")) {
+///     println!("{}", path.unwrap().display());
+/// }
+/// ```
+pub struct Walk {
+    /// 待处理的条目，栈顶是下一个要返回的
+    stack: Vec<(PathBuf, Rc<Ignore>, Option<Rc<Ancestor>>)>,
+    root: Option<PathBuf>,
+    /// 遍历的起点，用来计算 `filter` 用的相对路径
+    base: PathBuf,
+    filter: Filter,
+}
+
+impl Walk {
+    pub fn new<P: AsRef<Path>>(root: P) -> Walk {
+        Walk {
+            stack: Vec::new(),
+            root: Some(root.as_ref().to_path_buf()),
+            base: root.as_ref().to_path_buf(),
+            filter: Filter::new(),
+        }
+    }
+
+    /// 替换默认的过滤规则（不过滤任何文件）
+    pub fn with_filter(mut self, filter: Filter) -> Walk {
+        self.filter = filter;
+        self
+    }
+
+    fn push_dir(
+        &mut self,
+        dir: &Path,
+        parent: Option<Rc<Ignore>>,
+        ancestor: Option<Rc<Ancestor>>,
+    ) -> io::Result<()> {
+        let ignore = Ignore::load(dir, parent);
+        let mut entries = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        entries.sort();
+        for path in entries.into_iter().rev() {
+            self.stack
+                .push((path, Rc::clone(&ignore), ancestor.clone()));
+        }
+        Ok(())
+    }
+
+    /// 跟随符号链接时，目录的真实路径已经在上层出现过就是循环
+    fn enter(
+        &self,
+        dir: &Path,
+        ancestor: Option<Rc<Ancestor>>,
+    ) -> io::Result<Option<Rc<Ancestor>>> {
+        if !self.filter.is_following_links() {
+            return Ok(None);
+        }
+        let real = fs::canonicalize(dir)?;
+        if ancestor.as_deref().is_some_and(|a| a.contains(&real)) {
+            return Err(io::Error::other(format!(
+                "file system loop found: {} points to an ancestor {}",
+                dir.display(),
+                real.display()
+            )));
+        }
+        Ok(Some(Rc::new(Ancestor {
+            dir: real,
+            parent: ancestor,
+        })))
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(root) = self.root.take() {
+            // 显式给出的路径不受忽略规则和过滤规则影响
+            return match fs::metadata(&root) {
+                Ok(meta) if meta.is_dir() => {
+                    let pushed = self
+                        .enter(&root, None)
+                        .and_then(|ancestor| self.push_dir(&root, None, ancestor));
+                    match pushed {
+                        Ok(()) => self.next(),
+                        Err(e) => Some(Err(e)),
+                    }
+                }
+                Ok(_) => Some(Ok(root)),
+                Err(e) => Some(Err(e)),
+            };
+        }
+
+        while let Some((path, ignore, ancestor)) = self.stack.pop() {
+            let link = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => return Some(Err(e)),
+            };
+            // 默认不跟随指向目录的符号链接；指向不存在的文件的链接直接跳过
+            let meta = if link.file_type().is_symlink() {
+                match fs::metadata(&path) {
+                    Ok(meta) if meta.is_dir() && !self.filter.is_following_links() => continue,
+                    Ok(meta) => meta,
+                    Err(_) => continue,
+                }
+            } else {
+                link
+            };
+            let is_dir = meta.is_dir();
+            if path.file_name().is_some_and(|name| name == ".git") && is_dir {
+                continue;
+            }
+            if ignore.is_ignored(&path, is_dir) {
+                continue;
+            }
+            let relative = path
+                .strip_prefix(&self.base)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if !self.filter.is_match_metadata(&relative, &meta) {
+                continue;
+            }
+            if is_dir {
+                let pushed = self
+                    .enter(&path, ancestor)
+                    .and_then(|ancestor| self.push_dir(&path, Some(ignore), ancestor));
+                if let Err(e) = pushed {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            if !meta.is_file() {
+                continue;
+            }
+            return Some(Ok(path));
+        }
+        None
+    }
+}
+
+/// 开头 8KB 里出现 NUL 字节就认为是二进制文件
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8 * 1024).any(|&b| b == 0)
+}
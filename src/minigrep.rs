@@ -1,12 +1,21 @@
-use learning_rust::mgrep::{self, Config};
-use std::env;
+use learning_rust::mgrep::{self, args, Config};
+use std::{env, process};
 
 fn main() {
     let config = Config::new(env::args()).unwrap_or_else(|err| {
-        panic!("Error: {}", err);
+        if err.is_info() {
+            println!("{}", err);
+            process::exit(0);
+        }
+        eprintln!("minigrep: {}\n\n{}", err, args::usage());
+        process::exit(2);
     });
 
-    if let Err(e) = mgrep::run(config) {
-        panic!("{}", e);
+    match mgrep::run(config) {
+        Ok(status) => process::exit(status.code()),
+        Err(e) => {
+            eprintln!("minigrep: {}", e);
+            process::exit(2);
+        }
     }
 }
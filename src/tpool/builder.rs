@@ -0,0 +1,94 @@
+//! 按需增减 worker 的线程池的配置。
+
+use std::{thread, time::Duration};
+
+use super::{Overflow, ThreadPool};
+
+/// 创建 `ThreadPool` 的配置，用 `ThreadPool::builder()` 得到
+///
+/// 线程池启动时有 `min_threads` 个 worker；提交 job 时如果排队的 job 不少于空闲的 worker，
+/// 再启动一个，最多 `max_threads` 个。超过 `min_threads` 的 worker 空闲 `keep_alive` 之后退出。
+///
+/// ```
+/// use std::time::Duration;
+/// use learning_rust::tpool::{Overflow, ThreadPool};
+///
+/// let pool = ThreadPool::builder()
+///     .min_threads(1)
+///     .max_threads(8)
+///     .keep_alive(Duration::from_secs(5))
+///     .capacity(256, Overflow::CallerRuns)
+///     .build();
+/// assert_eq!(1, pool.thread_count());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Builder {
+    min_threads: usize,
+    max_threads: usize,
+    keep_alive: Duration,
+    capacity: usize,
+    overflow: Overflow,
+}
+
+impl Default for Builder {
+    /// 没有常驻的 worker，最多和 CPU 数一样多，空闲 60 秒退出，队列不限长度
+    fn default() -> Builder {
+        Builder {
+            min_threads: 0,
+            max_threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            keep_alive: Duration::from_secs(60),
+            capacity: usize::MAX,
+            overflow: Overflow::Block,
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// 一直保留的 worker 数，启动时就创建
+    pub fn min_threads(mut self, min: usize) -> Builder {
+        self.min_threads = min;
+        self
+    }
+
+    /// 最多同时有多少个 worker
+    pub fn max_threads(mut self, max: usize) -> Builder {
+        self.max_threads = max;
+        self
+    }
+
+    /// 超过 `min_threads` 的 worker 空闲多久以后退出
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Builder {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// 最多有 `capacity` 个 job 排队，队列满了时按 `overflow` 处理，见 `ThreadPool::bounded`
+    pub fn capacity(mut self, capacity: usize, overflow: Overflow) -> Builder {
+        self.capacity = capacity;
+        self.overflow = overflow;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// `max_threads` 或 `capacity` 为 0，或者 `min_threads` 大于 `max_threads` 时会 panic。
+    pub fn build(self) -> ThreadPool {
+        assert!(self.max_threads > 0, "max_threads must be positive");
+        assert!(
+            self.min_threads <= self.max_threads,
+            "min_threads must not exceed max_threads"
+        );
+        assert!(self.capacity > 0, "capacity must be positive");
+        ThreadPool::build(
+            self.min_threads,
+            self.max_threads,
+            self.keep_alive,
+            self.capacity,
+            self.overflow,
+        )
+    }
+}
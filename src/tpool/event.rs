@@ -0,0 +1,56 @@
+//! 线程池运行时交给 log hook 的事件。
+
+use std::fmt;
+
+use super::JobPanic;
+
+/// 线程池里发生的事，`Display` 输出一行日志
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// worker 开始执行一个 job
+    JobStarted { worker: usize },
+    /// job panic 了，并且没有设置 panic hook
+    JobPanicked(JobPanic),
+    /// 线程池关闭以后提交的 job 被丢掉了
+    JobRejected,
+    /// 队列满了，新提交的 job 被丢掉了（`Overflow::Reject`）
+    QueueFull,
+    /// 队列满了，丢掉了最早的一个 job（`Overflow::DropOldest`）
+    JobDropped,
+    /// 启动了一个 worker
+    WorkerStarted { worker: usize },
+    /// worker 空闲太久，或者 `resize` 以后线程太多，退出了
+    WorkerRetired { worker: usize },
+    /// worker 线程意外退出，在同一个位置重新启动了一个
+    WorkerRespawned { worker: usize },
+    /// worker 正常退出
+    WorkerExited { worker: usize },
+    /// 开始关闭：队列里的 job 执行完以后 worker 退出
+    Shutdown,
+    /// 立即关闭：丢掉了队列里的 `dropped` 个 job
+    ShutdownNow { dropped: usize },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::JobStarted { worker } => write!(f, "Worker {} got a job; executing.", worker),
+            Event::JobPanicked(panic) => {
+                write!(f, "Worker {} panicked: {}", panic.worker, panic.message)
+            }
+            Event::JobRejected => write!(f, "Rejected a job submitted after shutdown."),
+            Event::QueueFull => write!(f, "Rejected a job; the queue is full."),
+            Event::JobDropped => write!(f, "Dropped the oldest queued job; the queue is full."),
+            Event::WorkerStarted { worker } => write!(f, "Worker {} started.", worker),
+            Event::WorkerRetired { worker } => write!(f, "Worker {} retired.", worker),
+            Event::WorkerRespawned { worker } => {
+                write!(f, "Worker {} died; started a new one.", worker)
+            }
+            Event::WorkerExited { worker } => write!(f, "Worker {} was told to terminate.", worker),
+            Event::Shutdown => write!(f, "Shutting down all workers."),
+            Event::ShutdownNow { dropped } => {
+                write!(f, "Shutting down now; dropped {} queued job(s).", dropped)
+            }
+        }
+    }
+}
@@ -0,0 +1,724 @@
+//! 线程池。
+//!
+//! `new` 创建固定数量的 worker；`builder` 可以设置最少、最多的 worker 数，
+//! 排队的 job 多起来时按需启动新的 worker，空闲太久的 worker 退出，`resize` 可以随时改变线程数。
+//! 每个 worker 有自己的 job 队列，空闲时从全局队列取或者从别的 worker 那里偷，见 `scheduler`。
+//! job 在 `catch_unwind` 里执行，panic 不会带走 worker 线程，只会交给 panic hook；
+//! worker 线程因为其他原因（例如 panic hook 本身 panic）退出时，会在原来的位置上重新启动一个。
+//! 锁被 poison 时照常使用里面的数据：锁里只有 job 队列和线程句柄，不会处于不一致的状态。
+//!
+//! `execute` 只管执行；需要返回值时用 `spawn`，通过 `task::TaskHandle` 取回结果；
+//! 要借用栈上的数据时在 `scope` 里提交。
+//!
+//! 线程池本身不输出任何东西，运行信息以 `event::Event` 的形式交给 log hook。
+//! 关闭时可以用 `shutdown` 等队列里的 job 执行完，也可以用 `shutdown_now` 把它们取回来；
+//! drop 时等同于 `shutdown` 之后等待所有 worker 退出。
+//!
+//! `new` 的队列没有长度限制；`bounded` 限制排队的 job 数，队列满了时按 `Overflow` 处理。
+
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+pub mod builder;
+pub mod event;
+mod scheduler;
+pub mod scope;
+pub mod task;
+
+use builder::Builder;
+use event::Event;
+use scheduler::{Push, Scheduler};
+use scope::Scope;
+use task::{TaskError, TaskHandle};
+
+/// 提交给线程池的 job，`shutdown_now` 把还没执行的 job 以这个类型返回
+pub type Job = Box<dyn FnOnce() + Send + 'static>;
+
+type PanicHook = dyn Fn(&JobPanic) + Send + Sync + 'static;
+
+type LogHook = dyn Fn(&Event) + Send + Sync + 'static;
+
+/// `bounded` 的队列满了时怎么处理新提交的 job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// 等到队列里有空位
+    Block,
+    /// 丢掉新的 job；`try_execute` 会把它还回来
+    Reject,
+    /// 丢掉队列里最早的一个 job（worker 已经取到自己队列里的 job 不一定按提交的顺序），放进新的
+    DropOldest,
+    /// 在提交 job 的线程里直接执行，job 的 panic 也传给调用者
+    CallerRuns,
+}
+
+/// `try_execute` 没有提交成功，里面是原来的 job
+pub enum ExecuteError {
+    /// 队列满了
+    Full(Job),
+    /// 线程池已经关闭
+    Shutdown(Job),
+}
+
+impl ExecuteError {
+    /// 取回没有提交成功的 job
+    pub fn into_job(self) -> Job {
+        match self {
+            ExecuteError::Full(job) | ExecuteError::Shutdown(job) => job,
+        }
+    }
+}
+
+impl fmt::Debug for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecuteError::Full(_) => write!(f, "Full(..)"),
+            ExecuteError::Shutdown(_) => write!(f, "Shutdown(..)"),
+        }
+    }
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecuteError::Full(_) => write!(f, "thread pool queue is full"),
+            ExecuteError::Shutdown(_) => write!(f, "thread pool is shut down"),
+        }
+    }
+}
+
+impl Error for ExecuteError {}
+
+/// 一个 job panic 时交给 panic hook 的信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobPanic {
+    /// 执行这个 job 的 worker 的编号
+    pub worker: usize,
+    /// `panic!` 的消息；payload 不是字符串时是 `Box<dyn Any>`
+    pub message: String,
+}
+
+/// panic payload 里的消息
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        String::from("Box<dyn Any>")
+    }
+}
+
+/// 忽略 poison，锁里的数据始终是一致的
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 所有 worker 共享的状态
+struct Shared {
+    scheduler: Scheduler,
+    /// 按 worker 编号存放线程句柄，重启的 worker 会替换掉原来的
+    threads: Mutex<Vec<Option<thread::JoinHandle<()>>>>,
+    /// 哪些编号上有 worker；启动和退出 worker 时都要持有这个锁
+    slots: Mutex<Vec<bool>>,
+    /// 还没有退出 `run` 的 worker 数，只在持有 `slots` 的锁时修改
+    running: AtomicUsize,
+    min_threads: AtomicUsize,
+    max_threads: AtomicUsize,
+    keep_alive: Duration,
+    /// 没有在执行 job 的 worker 数，包括刚启动的
+    idle: AtomicUsize,
+    /// 和 `slots` 的锁配对，有 worker 退出时通知
+    exited: Condvar,
+    panic_hook: RwLock<Option<Arc<PanicHook>>>,
+    log_hook: RwLock<Option<Arc<LogHook>>>,
+    /// 设置过 log hook；每个 job 都要记日志，没有 hook 时不用去拿锁
+    logging: AtomicBool,
+    panics: AtomicUsize,
+    respawns: AtomicUsize,
+}
+
+impl Shared {
+    fn report(&self, panic: JobPanic) {
+        self.panics.fetch_add(1, Ordering::SeqCst);
+        let hook = self
+            .panic_hook
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        match hook {
+            Some(hook) => hook(&panic),
+            None => self.log(Event::JobPanicked(panic)),
+        }
+    }
+
+    /// 在一个空着的编号上启动 worker
+    fn start(self: &Arc<Shared>, slots: &mut Vec<bool>) {
+        let id = match slots.iter().position(|active| !active) {
+            Some(id) => id,
+            None => {
+                slots.push(false);
+                self.scheduler.add_worker(slots.len() - 1);
+                slots.len() - 1
+            }
+        };
+        slots[id] = true;
+        self.running.fetch_add(1, Ordering::SeqCst);
+        let thread = Worker::spawn(id, Arc::clone(self));
+        let mut threads = lock(&self.threads);
+        if threads.len() <= id {
+            threads.resize_with(id + 1, || None);
+        }
+        // 原来在这个编号上的线程已经退出了 `run`，不用再等它
+        threads[id] = Some(thread);
+        drop(threads);
+        self.log(Event::WorkerStarted { worker: id });
+    }
+
+    /// 启动 worker，直到有 `min_threads` 个，并且空闲的 worker 比排队的 job
+    /// 加上马上要放进队列的 `pending` 个多（不超过 `max_threads`）
+    fn grow(self: &Arc<Shared>, pending: usize) {
+        // 和 `retire` 配对：worker 退出以后再放进队列的 job，这里一定能看到少了的 worker
+        let backed_up = || self.scheduler.len() + pending > self.idle.load(Ordering::SeqCst);
+        // 大多数时候不需要启动 worker，不用去拿锁
+        let running = self.running.load(Ordering::SeqCst);
+        let enough = running >= self.min_threads.load(Ordering::Relaxed)
+            && (running >= self.max_threads.load(Ordering::Relaxed) || !backed_up());
+        if enough || !self.scheduler.is_running() {
+            return;
+        }
+        let mut slots = lock(&self.slots);
+        loop {
+            let running = self.running.load(Ordering::SeqCst);
+            let wanted = running < self.min_threads.load(Ordering::SeqCst)
+                || (running < self.max_threads.load(Ordering::SeqCst) && backed_up());
+            if !wanted || !self.scheduler.is_running() {
+                break;
+            }
+            self.start(&mut slots);
+        }
+    }
+
+    /// 第 `id` 个 worker 想要退出：`idle` 时 worker 数不能少于 `min_threads`，否则不能少于 `max_threads`
+    ///
+    /// 空闲退出时队列里还有 job 就不退出：它可能是在这个 worker 最后一次找 job 之后才放进来的，
+    /// 提交的线程又因为这个 worker 还算空闲而没有启动新的。
+    fn retire(&self, id: usize, idle: bool) -> bool {
+        let mut slots = lock(&self.slots);
+        let limit = if idle {
+            &self.min_threads
+        } else {
+            &self.max_threads
+        };
+        if self.running.load(Ordering::SeqCst) <= limit.load(Ordering::SeqCst)
+            || (idle && self.scheduler.len() > 0)
+        {
+            return false;
+        }
+        // 和 `running` 一起减，`grow` 不会把已经退出的 worker 算成空闲的
+        self.idle.fetch_sub(1, Ordering::SeqCst);
+        self.leave(id, &mut slots);
+        true
+    }
+
+    fn leave(&self, id: usize, slots: &mut [bool]) {
+        slots[id] = false;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        self.exited.notify_all();
+    }
+
+    /// 第 `id` 个 worker 执行一个 job，job 的 panic 交给 panic hook
+    fn run_job(&self, id: usize, job: Job) {
+        self.log(Event::JobStarted { worker: id });
+
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+            self.report(JobPanic {
+                worker: id,
+                message: panic_message(payload.as_ref()),
+            });
+        }
+    }
+
+    fn log(&self, event: Event) {
+        if !self.logging.load(Ordering::Relaxed) {
+            return;
+        }
+        let hook = self
+            .log_hook
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        // 日志出错不应该影响 job，也不能在 worker 退出时的 panic 里再 panic
+        if let Some(hook) = hook {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| hook(&event)));
+        }
+    }
+}
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+}
+
+impl ThreadPool {
+    /// 创建线程池。
+    ///
+    /// 线程池中线程的数量。
+    ///
+    /// # Panics
+    ///
+    /// `new` 函数在 size 为 0 时会 panic。
+    pub fn new(size: usize) -> ThreadPool {
+        ThreadPool::builder()
+            .min_threads(size)
+            .max_threads(size)
+            .build()
+    }
+
+    /// 设置线程数、空闲时间和队列长度，见 `builder::Builder`
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    /// 创建一个最多有 `capacity` 个 job 排队（不包括正在执行的）的线程池，
+    /// 队列满了时按 `overflow` 处理从线程池外面提交的 job。
+    ///
+    /// worker 在 job 里提交的 job 不受限制，否则 `Overflow::Block` 时可能互相等待。
+    ///
+    /// ```
+    /// use learning_rust::tpool::{Overflow, ThreadPool};
+    ///
+    /// let pool = ThreadPool::bounded(4, 64, Overflow::CallerRuns);
+    /// pool.execute(|| println!("runs on a worker, or here when 64 jobs are waiting"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// size 或 capacity 为 0 时会 panic。
+    pub fn bounded(size: usize, capacity: usize, overflow: Overflow) -> ThreadPool {
+        ThreadPool::builder()
+            .min_threads(size)
+            .max_threads(size)
+            .capacity(capacity, overflow)
+            .build()
+    }
+
+    fn build(
+        min_threads: usize,
+        max_threads: usize,
+        keep_alive: Duration,
+        capacity: usize,
+        overflow: Overflow,
+    ) -> ThreadPool {
+        let shared = Arc::new(Shared {
+            scheduler: Scheduler::new(capacity, overflow),
+            threads: Mutex::new(Vec::new()),
+            slots: Mutex::new(Vec::new()),
+            running: AtomicUsize::new(0),
+            min_threads: AtomicUsize::new(min_threads),
+            max_threads: AtomicUsize::new(max_threads),
+            keep_alive,
+            idle: AtomicUsize::new(0),
+            exited: Condvar::new(),
+            panic_hook: RwLock::new(None),
+            log_hook: RwLock::new(None),
+            logging: AtomicBool::new(false),
+            panics: AtomicUsize::new(0),
+            respawns: AtomicUsize::new(0),
+        });
+
+        shared.grow(0);
+
+        ThreadPool { shared }
+    }
+
+    /// 提交一个 job；在这个线程池的 job 里提交时放进当前 worker 自己的队列
+    ///
+    /// 线程池关闭以后提交的 job 会被丢掉（`shutdown` 之后在 job 里提交的除外）。
+    /// 队列满了时按 `bounded` 的 `Overflow` 处理，`Overflow::Block` 会一直等到有空位。
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+
+        if let Err(err) = self.submit(job, true) {
+            let event = match err {
+                ExecuteError::Full(_) => Event::QueueFull,
+                ExecuteError::Shutdown(_) => Event::JobRejected,
+            };
+            self.shared.log(event);
+        }
+    }
+
+    /// 和 `execute` 一样，但不会等待：队列满了时（`Overflow::Block` 和 `Overflow::Reject`）
+    /// 或者线程池已经关闭时把 job 放在错误里还回来
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use learning_rust::tpool::{ExecuteError, Overflow, ThreadPool};
+    ///
+    /// let pool = ThreadPool::bounded(1, 1, Overflow::Block);
+    /// let (go, wait) = mpsc::channel::<()>();
+    /// let (started, running) = mpsc::channel();
+    /// pool.execute(move || {
+    ///     started.send(()).unwrap();
+    ///     wait.recv().unwrap();
+    /// });
+    /// running.recv().unwrap();
+    /// pool.execute(|| {});
+    ///
+    /// let err = pool.try_execute(|| {}).unwrap_err();
+    /// assert!(matches!(err, ExecuteError::Full(_)));
+    /// go.send(()).unwrap();
+    /// ```
+    pub fn try_execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.submit(Box::new(f), false)
+    }
+
+    pub(crate) fn submit(&self, job: Job, block: bool) -> Result<(), ExecuteError> {
+        // 先启动 worker 再放进队列，`Overflow::Block` 等待空位之前也能多一个 worker
+        self.shared.grow(1);
+        match self.shared.scheduler.push(job, block) {
+            Push::Queued => {
+                // 上面检查之后可能有 worker 空闲退出了，再看一次，不让 job 留在没人执行的队列里
+                self.shared.grow(0);
+                Ok(())
+            }
+            Push::Displaced(oldest) => {
+                drop(oldest);
+                self.shared.log(Event::JobDropped);
+                self.shared.grow(0);
+                Ok(())
+            }
+            Push::Full(job) if self.shared.scheduler.overflow == Overflow::CallerRuns => {
+                job();
+                Ok(())
+            }
+            Push::Full(job) => Err(ExecuteError::Full(job)),
+            Push::Closed(job) => Err(ExecuteError::Shutdown(job)),
+        }
+    }
+
+    /// 和 `execute` 一样，但可以通过返回的句柄取回 `f` 的返回值
+    ///
+    /// `f` panic 时句柄得到 `TaskError::Panicked`，panic 同时也会交给 panic hook。
+    pub fn spawn<F, T>(&self, f: F) -> TaskHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, handle) = TaskHandle::new();
+        self.execute(move || match panic::catch_unwind(AssertUnwindSafe(f)) {
+            // 句柄被丢掉时没人关心结果
+            Ok(value) => {
+                let _ = sender.send(Ok(value));
+            }
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                let _ = sender.send(Err(TaskError::Panicked(message)));
+                panic::resume_unwind(payload);
+            }
+        });
+        handle
+    }
+
+    /// 创建一个 scope，在里面用 `Scope::spawn` 提交的 job 可以借用 `scope` 外面的数据
+    ///
+    /// `scope` 等到所有这些 job 结束才返回，即使 `f` panic 了也一样。
+    /// 有 job panic 时 `scope` 在所有 job 结束以后继续这个 panic；job 的 panic 不会交给 panic hook。
+    /// job 在执行之前被丢掉（`shutdown_now`、`Overflow::DropOldest`）时 `scope` 也会 panic。
+    /// 在这个线程池的 job 里调用时，等待期间当前 worker 会帮忙执行队列里的 job。
+    ///
+    /// ```
+    /// use learning_rust::tpool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(4);
+    /// let mut numbers = vec![1, 2, 3, 4, 5, 6, 7, 8];
+    /// let total: i32 = numbers.iter().sum();
+    /// pool.scope(|s| {
+    ///     for chunk in numbers.chunks_mut(3) {
+    ///         s.spawn(move || chunk.iter_mut().for_each(|n| *n *= total));
+    ///     }
+    /// });
+    /// assert_eq!(vec![36, 72, 108, 144, 180, 216, 252, 288], numbers);
+    /// ```
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where
+        F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+    {
+        let scope = Scope::new(self);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.wait();
+        let value = result.unwrap_or_else(|payload| panic::resume_unwind(payload));
+        scope.finish();
+        value
+    }
+
+    /// 设置 job panic 时调用的函数，替换掉原来的
+    ///
+    /// 没有设置时 panic 以 `Event::JobPanicked` 交给 log hook
+    /// （标准库默认的 panic hook 仍然会输出 `thread '...' panicked at ...`）。
+    /// hook 在 worker 线程里执行；hook 本身 panic 时这个 worker 会被重新启动。
+    ///
+    /// ```
+    /// use std::sync::mpsc;
+    /// use learning_rust::tpool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(2);
+    /// let (tx, rx) = mpsc::channel();
+    /// pool.set_panic_hook(move |panic| tx.send(panic.message.clone()).unwrap());
+    /// pool.execute(|| panic!("boom"));
+    /// assert_eq!("boom", rx.recv().unwrap());
+    /// ```
+    pub fn set_panic_hook<F>(&self, hook: F)
+    where
+        F: Fn(&JobPanic) + Send + Sync + 'static,
+    {
+        *self
+            .shared
+            .panic_hook
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(hook));
+    }
+
+    /// 设置接收运行信息的函数，替换掉原来的；没有设置时什么都不输出
+    ///
+    /// hook 可能在任意线程里执行，包括 worker 线程和调用 `execute`、`shutdown` 的线程；
+    /// hook 本身的 panic 会被忽略。
+    ///
+    /// ```
+    /// use learning_rust::tpool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(2);
+    /// pool.set_log_hook(|event| eprintln!("{}", event));
+    /// ```
+    pub fn set_log_hook<F>(&self, hook: F)
+    where
+        F: Fn(&Event) + Send + Sync + 'static,
+    {
+        *self
+            .shared
+            .log_hook
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(hook));
+        self.shared.logging.store(true, Ordering::SeqCst);
+    }
+
+    /// 开始关闭线程池，不等待
+    ///
+    /// 已经在队列里的 job，以及它们执行时再提交的 job，都会执行完；
+    /// 之后从线程池外面提交的 job 会被丢掉。worker 在队列空了以后退出，可以用 `join_timeout` 等待。
+    pub fn shutdown(&self) {
+        if self.shared.scheduler.shutdown() {
+            self.shared.log(Event::Shutdown);
+        }
+    }
+
+    /// 立即关闭线程池，返回还没有开始执行的 job
+    ///
+    /// 正在执行的 job 会执行完，之后 worker 退出；返回的 job 被丢掉时，
+    /// 对应的 `TaskHandle` 得到 `TaskError::Canceled`。
+    ///
+    /// ```
+    /// use std::{sync::mpsc, time::Duration};
+    /// use learning_rust::tpool::{task::TaskError, ThreadPool};
+    ///
+    /// let pool = ThreadPool::new(1);
+    /// let (go, wait) = mpsc::channel::<()>();
+    /// pool.execute(move || wait.recv().unwrap());
+    /// let queued = pool.spawn(|| 1);
+    ///
+    /// let pending = pool.shutdown_now();
+    /// go.send(()).unwrap();
+    /// drop(pending);
+    /// assert_eq!(Err(TaskError::Canceled), queued.join());
+    /// assert!(pool.join_timeout(Duration::from_secs(10)));
+    /// ```
+    pub fn shutdown_now(&self) -> Vec<Job> {
+        let jobs = self.shared.scheduler.stop();
+        self.shared.log(Event::ShutdownNow {
+            dropped: jobs.len(),
+        });
+        jobs
+    }
+
+    /// 最多等待 `timeout`，直到所有 worker 退出；返回 `true` 表示都已经退出
+    ///
+    /// 没有调用 `shutdown` 或 `shutdown_now` 时 worker 不会退出，只会等到超时。
+    pub fn join_timeout(&self, timeout: Duration) -> bool {
+        let running = &self.shared.running;
+        let slots = lock(&self.shared.slots);
+        let (slots, _) = self
+            .shared
+            .exited
+            .wait_timeout_while(slots, timeout, |_| running.load(Ordering::SeqCst) > 0)
+            .unwrap_or_else(PoisonError::into_inner);
+        if running.load(Ordering::SeqCst) > 0 {
+            return false;
+        }
+        drop(slots);
+        self.join_threads();
+        true
+    }
+
+    /// 改变最少、最多的 worker 数
+    ///
+    /// 不够 `min_threads` 时立即启动；超过 `max_threads` 的 worker 在手上的 job
+    /// （包括自己队列里的）执行完以后退出，不会丢掉 job。
+    ///
+    /// ```
+    /// use learning_rust::tpool::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(2);
+    /// pool.resize(4, 4);
+    /// assert_eq!(4, pool.thread_count());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// `max_threads` 为 0 或者 `min_threads` 大于 `max_threads` 时会 panic。
+    pub fn resize(&self, min_threads: usize, max_threads: usize) {
+        assert!(max_threads > 0, "max_threads must be positive");
+        assert!(
+            min_threads <= max_threads,
+            "min_threads must not exceed max_threads"
+        );
+        {
+            // 和 `retire` 互斥，worker 看到的两个值总是一致的
+            let _slots = lock(&self.shared.slots);
+            self.shared.min_threads.store(min_threads, Ordering::SeqCst);
+            self.shared.max_threads.store(max_threads, Ordering::SeqCst);
+        }
+        self.shared.grow(0);
+        self.shared.scheduler.wake_workers();
+    }
+
+    /// 现在的 worker 数
+    pub fn thread_count(&self) -> usize {
+        self.shared.running.load(Ordering::SeqCst)
+    }
+
+    fn join_threads(&self) {
+        let size = lock(&self.shared.threads).len();
+        for id in 0..size {
+            // worker 退出前可能已经换上了重新启动的线程，要一直等到这个位置空了为止；
+            // 等待时不能持有锁，重新启动的线程要往里面写
+            loop {
+                let thread = lock(&self.shared.threads)[id].take();
+                match thread {
+                    Some(thread) => {
+                        let _ = thread.join();
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// 到目前为止 panic 的 job 数
+    pub fn panic_count(&self) -> usize {
+        self.shared.panics.load(Ordering::SeqCst)
+    }
+
+    /// 到目前为止重新启动的 worker 数
+    pub fn respawn_count(&self) -> usize {
+        self.shared.respawns.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ThreadPool {
+    /// 队列里已经有的 job（包括它们执行时再提交的）都执行完以后 worker 才会退出
+    fn drop(&mut self) {
+        self.shutdown();
+        self.join_threads();
+    }
+}
+
+struct Worker {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Worker {
+    fn spawn(id: usize, shared: Arc<Shared>) -> thread::JoinHandle<()> {
+        // 新的 worker 在取到 job 之前是空闲的
+        shared.idle.fetch_add(1, Ordering::SeqCst);
+        let worker = Worker { id, shared };
+        thread::Builder::new()
+            .name(format!("tpool-worker-{}", id))
+            .spawn(move || worker.run())
+            .expect("failed to spawn worker thread")
+    }
+
+    fn run(&self) {
+        let id = self.id;
+        let shared = &self.shared;
+        let scheduler = &shared.scheduler;
+        scheduler.register(id);
+        // 从什么时候开始没有 job 可做；执行 job 时是 `None`
+        let mut idle_since = Some(Instant::now());
+        loop {
+            // `shutdown_now` 之后不再取新的 job
+            if scheduler.is_stopped() {
+                break;
+            }
+            // 线程数被 `resize` 调小了，自己队列里的 job 执行完就退出
+            let too_many =
+                shared.running.load(Ordering::Relaxed) > shared.max_threads.load(Ordering::Relaxed);
+            if too_many && scheduler.is_local_empty(id) && shared.retire(id, false) {
+                return self.retired();
+            }
+            let job = match scheduler.find(id) {
+                Some(job) => job,
+                None => {
+                    let idle_for = idle_since.get_or_insert_with(Instant::now).elapsed();
+                    if idle_for >= shared.keep_alive && shared.retire(id, true) {
+                        return self.retired();
+                    }
+                    match scheduler.wait(shared.keep_alive.saturating_sub(idle_for)) {
+                        Some(false) => break,
+                        Some(true) | None => continue,
+                    }
+                }
+            };
+
+            shared.idle.fetch_sub(1, Ordering::Relaxed);
+            shared.run_job(id, job);
+            shared.idle.fetch_add(1, Ordering::Relaxed);
+            idle_since = None;
+        }
+
+        shared.idle.fetch_sub(1, Ordering::SeqCst);
+        shared.log(Event::WorkerExited { worker: id });
+        shared.leave(id, &mut lock(&shared.slots));
+    }
+
+    /// `retire` 已经把自己从 `running` 和 `idle` 里去掉了
+    fn retired(&self) {
+        self.shared.log(Event::WorkerRetired { worker: self.id });
+    }
+}
+
+impl Drop for Worker {
+    /// 线程因为 panic 退出时在同一个位置启动一个新的 worker
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.shared.respawns.fetch_add(1, Ordering::SeqCst);
+            self.shared.log(Event::WorkerRespawned { worker: self.id });
+            let thread = Worker::spawn(self.id, Arc::clone(&self.shared));
+            lock(&self.shared.threads)[self.id] = Some(thread);
+        }
+    }
+}
@@ -0,0 +1,328 @@
+//! work-stealing 调度。
+//!
+//! 每个 worker 有自己的双端队列，另外有一个全局队列（injector）接收线程池外面提交的 job：
+//!
+//! - worker 在 job 里再提交的 job 放进自己队列的尾部，之后从尾部取（LIFO），刚产生的数据还在缓存里
+//! - 自己的队列空了，从 injector 一次搬一批过来，减少对 injector 的争用
+//! - injector 也空了，从其他 worker 队列的头部偷一个（最早放进去的，通常也是最大的一块工作）
+//! - 哪里都没有 job 时在条件变量上睡眠，提交 job 时只有确实有人在睡才去唤醒
+//!
+//! 所有的锁都只在操作队列的一瞬间持有，不会在等待的时候持有。
+//!
+//! 关闭分两种：`shutdown` 之后只接受 worker 在 job 里提交的 job，队列空了 worker 才退出；
+//! `stop` 之后什么都不接受，队列里剩下的 job 被取出来交给调用者。
+//!
+//! 队列按 worker 编号存放，worker 退出以后它的位置留着给以后启动的 worker 用，偷 job 时也会查看。
+//!
+//! 有容量限制时只限制从线程池外面提交的 job：worker 在 job 里提交的总是直接放进自己的队列，
+//! 否则 `Overflow::Block` 时 worker 会等自己腾出空间，永远等不到。
+
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicIsize, AtomicU8, AtomicUsize, Ordering},
+        Condvar, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+use super::{lock, Job, Overflow};
+
+/// 从 injector 一次最多搬多少个 job
+const BATCH: usize = 32;
+
+/// 睡眠之前最多让出几次 CPU
+const SPINS: usize = 16;
+
+const RUNNING: u8 = 0;
+const DRAINING: u8 = 1;
+const STOPPED: u8 = 2;
+
+thread_local! {
+    /// 当前线程是哪个调度器（用地址区分）的第几个 worker
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+pub(crate) struct Scheduler {
+    injector: Mutex<VecDeque<Job>>,
+    /// 按 worker 编号存放，只增不减
+    locals: RwLock<Vec<Mutex<VecDeque<Job>>>>,
+    /// 所有队列里的 job 总数；先放进队列再加一，所以可能短暂地是负数
+    queued: AtomicIsize,
+    sleeping: AtomicUsize,
+    sleep: Mutex<()>,
+    wake: Condvar,
+    state: AtomicU8,
+    capacity: usize,
+    pub(crate) overflow: Overflow,
+    /// 因为 `Overflow::Block` 在 `space` 上等待的线程数
+    blocked: AtomicUsize,
+    /// 和 `injector` 的锁配对，队列有空位时通知
+    space: Condvar,
+}
+
+/// `push` 的结果
+pub(crate) enum Push {
+    Queued,
+    /// 放进去了，但是为了腾出位置挤掉了最早的 job
+    Displaced(Job),
+    /// 队列满了
+    Full(Job),
+    /// 已经关闭
+    Closed(Job),
+}
+
+impl Scheduler {
+    /// `capacity` 为 `usize::MAX` 时不限制队列长度
+    pub(crate) fn new(capacity: usize, overflow: Overflow) -> Scheduler {
+        Scheduler {
+            injector: Mutex::new(VecDeque::new()),
+            locals: RwLock::new(Vec::new()),
+            queued: AtomicIsize::new(0),
+            sleeping: AtomicUsize::new(0),
+            sleep: Mutex::new(()),
+            wake: Condvar::new(),
+            state: AtomicU8::new(RUNNING),
+            capacity,
+            overflow,
+            blocked: AtomicUsize::new(0),
+            space: Condvar::new(),
+        }
+    }
+
+    fn id(&self) -> usize {
+        self as *const Scheduler as usize
+    }
+
+    /// 给第 `index` 个 worker 准备好队列；不能在持有 `locals` 的读锁时调用
+    pub(crate) fn add_worker(&self, index: usize) {
+        let mut locals = self.locals.write().unwrap_or_else(PoisonError::into_inner);
+        while locals.len() <= index {
+            locals.push(Mutex::new(VecDeque::new()));
+        }
+    }
+
+    /// 同一个线程不能同时持有两个读锁，`add_worker` 在等写锁时第二个会死锁
+    fn locals(&self) -> RwLockReadGuard<'_, Vec<Mutex<VecDeque<Job>>>> {
+        self.locals.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// 把当前线程登记为第 `index` 个 worker
+    pub(crate) fn register(&self, index: usize) {
+        WORKER.with(|worker| worker.set(Some((self.id(), index))));
+    }
+
+    /// 当前线程是这个调度器的 worker 时返回它的编号
+    pub(crate) fn current(&self) -> Option<usize> {
+        WORKER
+            .with(Cell::get)
+            .and_then(|(id, index)| (id == self.id()).then_some(index))
+    }
+
+    /// 放进队列；队列满了时按 `overflow` 处理，`block` 为 `false` 时 `Overflow::Block` 也不等待
+    ///
+    /// `Overflow::CallerRuns` 和 `Overflow::Reject` 一样返回 `Push::Full`，由调用者决定怎么执行。
+    pub(crate) fn push(&self, job: Job, block: bool) -> Push {
+        // 在队列的锁里检查状态，`stop` 取队列时不会漏掉这里放进去的 job
+        let pushed = match self.current() {
+            Some(index) => {
+                let locals = self.locals();
+                let mut local = lock(&locals[index]);
+                if self.state() == STOPPED {
+                    return Push::Closed(job);
+                }
+                local.push_back(job);
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                Push::Queued
+            }
+            None => self.push_injector(job, block),
+        };
+        if let Push::Queued = pushed {
+            // 和 `wait` 里先加 `sleeping` 再检查 `queued` 配对：两边至少有一边能看到对方
+            if self.sleeping.load(Ordering::SeqCst) > 0 {
+                let _guard = lock(&self.sleep);
+                self.wake.notify_one();
+            }
+        }
+        pushed
+    }
+
+    /// 从线程池外面提交；在 `injector` 的锁里检查和更新 `queued`，提交的线程之间不会超出容量
+    fn push_injector(&self, job: Job, block: bool) -> Push {
+        let mut injector = lock(&self.injector);
+        loop {
+            if self.state() != RUNNING {
+                return Push::Closed(job);
+            }
+            if self.len() < self.capacity {
+                injector.push_back(job);
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                return Push::Queued;
+            }
+            match self.overflow {
+                Overflow::Block if block => {
+                    // 和 `find` 里先减 `queued` 再检查 `blocked` 配对
+                    self.blocked.fetch_add(1, Ordering::SeqCst);
+                    if self.len() >= self.capacity {
+                        injector = self
+                            .space
+                            .wait(injector)
+                            .unwrap_or_else(PoisonError::into_inner);
+                    }
+                    self.blocked.fetch_sub(1, Ordering::SeqCst);
+                }
+                Overflow::DropOldest => {
+                    let oldest = injector.pop_front().or_else(|| {
+                        self.locals()
+                            .iter()
+                            .find_map(|local| lock(local).pop_front())
+                    });
+                    let Some(oldest) = oldest else {
+                        // 队列里的 job 都是别的线程刚放进去还没来得及计数的，只能拒绝
+                        return Push::Full(job);
+                    };
+                    injector.push_back(job);
+                    return Push::Displaced(oldest);
+                }
+                _ => return Push::Full(job),
+            }
+        }
+    }
+
+    /// 排队的 job 数
+    pub(crate) fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst).max(0) as usize
+    }
+
+    fn state(&self) -> u8 {
+        self.state.load(Ordering::SeqCst)
+    }
+
+    /// 还没有调用过 `shutdown` 或 `stop`
+    pub(crate) fn is_running(&self) -> bool {
+        self.state() == RUNNING
+    }
+
+    /// 调用过 `stop`，worker 不应该再取 job
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.state() == STOPPED
+    }
+
+    /// 给第 `index` 个 worker 找一个 job
+    pub(crate) fn find(&self, index: usize) -> Option<Job> {
+        let job = self.pop_local(index).or_else(|| self.pop_injector(index));
+        let job = job.or_else(|| self.steal(index))?;
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        if self.blocked.load(Ordering::SeqCst) > 0 {
+            let _guard = lock(&self.injector);
+            self.space.notify_one();
+        }
+        Some(job)
+    }
+
+    fn pop_local(&self, index: usize) -> Option<Job> {
+        lock(&self.locals()[index]).pop_back()
+    }
+
+    /// 第 `index` 个 worker 自己的队列是空的，它退出时不会把 job 留在里面
+    pub(crate) fn is_local_empty(&self, index: usize) -> bool {
+        lock(&self.locals()[index]).is_empty()
+    }
+
+    /// 取一个，再按 worker 数平分剩下的，顺手搬一批到自己的队列里
+    fn pop_injector(&self, index: usize) -> Option<Job> {
+        let mut injector = lock(&self.injector);
+        let job = injector.pop_front()?;
+        let locals = self.locals();
+        let batch = (injector.len() / locals.len()).min(BATCH);
+        if batch > 0 {
+            let mut local = lock(&locals[index]);
+            // 倒着放进尾部，从尾部取的时候还是原来的顺序
+            for job in injector.drain(..batch).rev() {
+                local.push_back(job);
+            }
+        }
+        Some(job)
+    }
+
+    fn steal(&self, index: usize) -> Option<Job> {
+        let locals = self.locals();
+        let n = locals.len();
+        (1..n).find_map(|k| lock(&locals[(index + k) % n]).pop_front())
+    }
+
+    /// 还有 job 要执行时返回 `Some(true)`，应该退出时返回 `Some(false)`
+    fn ready(&self) -> Option<bool> {
+        match self.state() {
+            STOPPED => Some(false),
+            _ if self.queued.load(Ordering::SeqCst) > 0 => Some(true),
+            DRAINING => Some(false),
+            _ => None,
+        }
+    }
+
+    /// 没有 job 时最多睡眠 `timeout`，直到有新的 job 或者关闭
+    ///
+    /// 有 job 时返回 `Some(true)`，应该退出时返回 `Some(false)`；
+    /// 超时或者被 `wake_workers` 叫醒时返回 `None`，由 worker 决定是不是要退出。
+    pub(crate) fn wait(&self, timeout: Duration) -> Option<bool> {
+        // 先让出几次 CPU 再睡，job 一个接一个提交时不用每次都唤醒
+        for _ in 0..SPINS {
+            if let Some(keep_running) = self.ready() {
+                return Some(keep_running);
+            }
+            thread::yield_now();
+        }
+        let guard = lock(&self.sleep);
+        self.sleeping.fetch_add(1, Ordering::SeqCst);
+        let ready = match self.ready() {
+            Some(keep_running) => Some(keep_running),
+            None => {
+                let _guard = self
+                    .wake
+                    .wait_timeout(guard, timeout)
+                    .unwrap_or_else(PoisonError::into_inner);
+                self.ready()
+            }
+        };
+        self.sleeping.fetch_sub(1, Ordering::SeqCst);
+        ready
+    }
+
+    /// 叫醒所有睡眠的 worker，例如线程数变了，让它们检查自己是不是应该退出
+    pub(crate) fn wake_workers(&self) {
+        let _guard = lock(&self.sleep);
+        self.wake.notify_all();
+    }
+
+    /// 队列里剩下的 job 执行完以后让所有 worker 退出；返回 `false` 表示之前已经关闭过了
+    pub(crate) fn shutdown(&self) -> bool {
+        let changed = self
+            .state
+            .compare_exchange(RUNNING, DRAINING, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        self.wake_all();
+        changed
+    }
+
+    /// 让所有 worker 执行完手上的 job 就退出，返回还没有执行的 job
+    pub(crate) fn stop(&self) -> Vec<Job> {
+        self.state.store(STOPPED, Ordering::SeqCst);
+        let mut jobs: Vec<Job> = lock(&self.injector).drain(..).collect();
+        for local in self.locals().iter() {
+            jobs.extend(lock(local).drain(..));
+        }
+        self.queued.fetch_sub(jobs.len() as isize, Ordering::SeqCst);
+        self.wake_all();
+        jobs
+    }
+
+    /// 唤醒所有睡眠的 worker 和等待空位的提交者，让它们重新检查状态
+    fn wake_all(&self) {
+        self.wake_workers();
+        let _guard = lock(&self.injector);
+        self.space.notify_all();
+    }
+}
@@ -0,0 +1,148 @@
+//! `ThreadPool::scope`：可以借用调用者栈上数据的 job。
+//!
+//! 和 `std::thread::scope` 一样，`scope` 返回之前一定会等所有在里面提交的 job 结束，
+//! 所以 job 只需要活得比 `'scope` 久，不需要 `'static`。
+//! 等待是靠计数：每个 job 结束或者没执行就被丢掉时计数减一，计数为零 `scope` 才返回。
+//! 所以即使 job 被 `shutdown_now` 取走了，`scope` 也会等到它被执行或者被丢掉为止。
+
+use std::{
+    any::Any,
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+use super::{lock, Job, ThreadPool};
+
+/// worker 在 `scope` 里等待时，多久看一次有没有可以帮忙执行的 job
+const HELP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// 一个 scope 里所有 job 共享的状态
+#[derive(Default)]
+struct State {
+    /// 还没有结束的 job 数
+    pending: Mutex<usize>,
+    finished: Condvar,
+    /// 第一个 panic 的 job 的 payload
+    panic: Mutex<Option<Box<dyn Any + Send>>>,
+    /// 有 job 没执行就被丢掉了
+    dropped: Mutex<usize>,
+}
+
+/// `ThreadPool::scope` 传给闭包的参数，用来提交借用了外面数据的 job
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope ThreadPool,
+    state: Arc<State>,
+    /// 和 `std::thread::Scope` 一样让 `'scope` 和 `'env` 都是不变的
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+/// 包着一个 scoped job，不管是执行完还是被丢掉，都在 `f` 被 drop 之后才让计数减一
+struct ScopedJob<'scope> {
+    f: Option<Box<dyn FnOnce() + Send + 'scope>>,
+    state: Arc<State>,
+}
+
+impl ScopedJob<'_> {
+    fn run(mut self) {
+        if let Some(f) = self.f.take() {
+            if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+                lock(&self.state.panic).get_or_insert(payload);
+            }
+        }
+    }
+}
+
+impl Drop for ScopedJob<'_> {
+    fn drop(&mut self) {
+        if self.f.take().is_some() {
+            *lock(&self.state.dropped) += 1;
+        }
+        let mut pending = lock(&self.state.pending);
+        *pending -= 1;
+        if *pending == 0 {
+            self.state.finished.notify_all();
+        }
+    }
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    pub(crate) fn new(pool: &'scope ThreadPool) -> Scope<'scope, 'env> {
+        Scope {
+            pool,
+            state: Arc::new(State::default()),
+            scope: PhantomData,
+            env: PhantomData,
+        }
+    }
+
+    /// 提交一个 job，它可以借用 `scope` 外面的数据
+    ///
+    /// 线程池已经关闭或者队列满了不能提交时，在当前线程里直接执行。
+    /// job 的 panic 会在 `scope` 结束时传给调用者。
+    pub fn spawn<F>(&'scope self, f: F)
+    where
+        F: FnOnce() + Send + 'scope,
+    {
+        *lock(&self.state.pending) += 1;
+        let job = ScopedJob {
+            f: Some(Box::new(f)),
+            state: Arc::clone(&self.state),
+        };
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || job.run());
+        // SAFETY: `ThreadPool::scope` 返回之前会等到 `pending` 为零，
+        // 也就是这个 job 已经执行完或者已经被 drop，它借用的数据在那之前一直有效
+        let job: Job = unsafe { mem::transmute(job) };
+        if let Err(err) = self.pool.submit(job, true) {
+            err.into_job()();
+        }
+    }
+
+    /// 等到所有 job 结束；在这个线程池的 worker 里调用时帮忙执行队列里的 job，
+    /// 否则只有一个 worker 时会永远等下去
+    pub(crate) fn wait(&self) {
+        let shared = &self.pool.shared;
+        let worker = shared.scheduler.current();
+        let mut pending = lock(&self.state.pending);
+        while *pending > 0 {
+            match worker {
+                Some(id) => {
+                    drop(pending);
+                    match shared.scheduler.find(id) {
+                        Some(job) => shared.run_job(id, job),
+                        None => {
+                            let pending = lock(&self.state.pending);
+                            let _ = self
+                                .state
+                                .finished
+                                .wait_timeout_while(pending, HELP_INTERVAL, |n| *n > 0)
+                                .unwrap_or_else(PoisonError::into_inner);
+                        }
+                    }
+                    pending = lock(&self.state.pending);
+                }
+                None => {
+                    pending = self
+                        .state
+                        .finished
+                        .wait(pending)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+    }
+
+    /// 所有 job 都结束以后调用：有 job panic 时继续 panic，有 job 被丢掉时也 panic
+    pub(crate) fn finish(&self) {
+        if let Some(payload) = lock(&self.state.panic).take() {
+            panic::resume_unwind(payload);
+        }
+        let dropped = *lock(&self.state.dropped);
+        if dropped > 0 {
+            panic!("{} scoped job(s) were dropped before they ran", dropped);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! `ThreadPool::spawn` 返回的句柄，用来取回 job 的返回值。
+
+use std::{
+    error::Error,
+    fmt,
+    sync::mpsc::{self, RecvTimeoutError, TryRecvError},
+    time::Duration,
+};
+
+/// 取不到返回值的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskError {
+    /// job panic 了，里面是 panic 的消息
+    Panicked(String),
+    /// job 还没执行就被丢掉了，例如线程池被立即关闭
+    Canceled,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TaskError::Panicked(message) => write!(f, "task panicked: {}", message),
+            TaskError::Canceled => write!(f, "task was canceled before it ran"),
+        }
+    }
+}
+
+impl Error for TaskError {}
+
+/// 一个 job 的返回值，类似 `std::thread::JoinHandle`
+///
+/// 丢掉句柄不会取消 job，只是不再关心它的结果。
+/// `try_join` / `join_timeout` 返回 `Some` 以后结果就被取走了，不能再取。
+///
+/// ```
+/// use std::time::Duration;
+/// use learning_rust::tpool::{task::TaskError, ThreadPool};
+///
+/// let pool = ThreadPool::new(2);
+/// let mut sum = pool.spawn(|| (1..=10).sum::<i32>());
+/// assert_eq!(Some(Ok(55)), sum.join_timeout(Duration::from_secs(10)));
+///
+/// let failed = pool.spawn(|| -> i32 { panic!("boom") });
+/// assert_eq!(Err(TaskError::Panicked(String::from("boom"))), failed.join());
+/// ```
+#[derive(Debug)]
+pub struct TaskHandle<T> {
+    receiver: mpsc::Receiver<Result<T, TaskError>>,
+    taken: bool,
+}
+
+impl<T> TaskHandle<T> {
+    /// 新建一个句柄和 worker 那一端用来发送结果的 `Sender`
+    pub(crate) fn new() -> (mpsc::Sender<Result<T, TaskError>>, TaskHandle<T>) {
+        let (sender, receiver) = mpsc::channel();
+        let handle = TaskHandle {
+            receiver,
+            taken: false,
+        };
+        (sender, handle)
+    }
+
+    /// 等待 job 结束
+    pub fn join(mut self) -> Result<T, TaskError> {
+        self.take();
+        self.receiver.recv().unwrap_or(Err(TaskError::Canceled))
+    }
+
+    /// 不等待；job 还没结束时返回 `None`
+    ///
+    /// # Panics
+    ///
+    /// 结果已经被取走时会 panic。
+    pub fn try_join(&mut self) -> Option<Result<T, TaskError>> {
+        assert!(!self.taken, "task result was already taken");
+        let result = match self.receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err(TaskError::Canceled),
+        };
+        self.take();
+        Some(result)
+    }
+
+    /// 最多等待 `timeout`；超时时返回 `None`，之后还可以继续等
+    ///
+    /// # Panics
+    ///
+    /// 结果已经被取走时会 panic。
+    pub fn join_timeout(&mut self, timeout: Duration) -> Option<Result<T, TaskError>> {
+        assert!(!self.taken, "task result was already taken");
+        let result = match self.receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => return None,
+            Err(RecvTimeoutError::Disconnected) => Err(TaskError::Canceled),
+        };
+        self.take();
+        Some(result)
+    }
+
+    fn take(&mut self) {
+        assert!(!self.taken, "task result was already taken");
+        self.taken = true;
+    }
+}
@@ -1,16 +1,33 @@
 use std::{
+    fmt::Write as _,
     fs,
-    io::{Read, Write},
+    io::{self, Read, Write},
     net::{TcpListener, TcpStream},
+    path::Path,
     thread,
     time::Duration,
 };
 
-use learning_rust::tpool::ThreadPool;
+use learning_rust::{
+    mgrep::{
+        matcher::Literal,
+        searcher::{Searcher, Sink},
+        Match,
+    },
+    tpool::{Overflow, ThreadPool},
+};
+
+/// `/search` 搜索的文件
+const SEARCH_FILE: &str = "hello.txt";
+/// `/search` 最多返回的行数
+const MAX_RESULTS: usize = 100;
+/// 最多有多少个连接在排队；排满了就先不 accept，让连接留在内核的 backlog 里
+const MAX_PENDING: usize = 64;
 
 fn main() {
     let listener = TcpListener::bind("127.0.0.1:8080").unwrap();
-    let pool = ThreadPool::new(4);
+    let pool = ThreadPool::bounded(4, MAX_PENDING, Overflow::Block);
+    pool.set_log_hook(|event| eprintln!("{}", event));
 
     // for stream in listener.incoming().take(2) {
     for stream in listener.incoming() {
@@ -31,20 +48,109 @@ fn handle_connection(mut stream: TcpStream) {
 
     let get = b"GET / HTTP/1.1\r\n";
     let sleep = b"GET /sleep HTTP/1.1\r\n";
+    let search = b"GET /search?";
 
-    let (status_line, filename) = if buffer.starts_with(get) {
-        ("HTTP/1.1 200 OK\r\n\r\n", "hello.html")
+    let (status_line, contents) = if buffer.starts_with(get) {
+        (
+            "HTTP/1.1 200 OK\r\n\r\n",
+            fs::read_to_string("hello.html").unwrap(),
+        )
     } else if buffer.starts_with(sleep) {
         thread::sleep(Duration::from_secs(5));
-        ("HTTP/1.1 200 OK\r\n\r\n", "hello.html")
+        (
+            "HTTP/1.1 200 OK\r\n\r\n",
+            fs::read_to_string("hello.html").unwrap(),
+        )
+    } else if buffer.starts_with(search) {
+        match search_page(&buffer[search.len()..]) {
+            Ok(page) => ("HTTP/1.1 200 OK\r\n\r\n", page),
+            Err(e) => ("HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n", e.to_string()),
+        }
     } else {
-        ("HTTP/1.1 404 NOT FOUND\r\n\r\n", "404.html")
+        (
+            "HTTP/1.1 404 NOT FOUND\r\n\r\n",
+            fs::read_to_string("404.html").unwrap(),
+        )
     };
 
-    let contents = fs::read_to_string(filename).unwrap();
-
     let response = format!("{}{}", status_line, contents);
 
     let _ = stream.write(response.as_bytes()).unwrap();
     stream.flush().unwrap();
 }
+
+/// 把匹配的行拼成 HTML 列表
+#[derive(Default)]
+struct HtmlList {
+    items: String,
+    count: usize,
+}
+
+impl Sink for HtmlList {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        let _ = writeln!(
+            self.items,
+            "<li>{}: {}</li>",
+            m.line_number,
+            escape_html(m.line)
+        );
+        self.count += 1;
+        Ok(self.count < MAX_RESULTS)
+    }
+}
+
+/// `GET /search?q=xxx`：不区分大小写地在 `SEARCH_FILE` 里搜索 `q`
+fn search_page(query_string: &[u8]) -> io::Result<String> {
+    let end = query_string
+        .iter()
+        .position(|&b| b == b' ')
+        .unwrap_or(query_string.len());
+    let query = query_string[..end]
+        .split(|&b| b == b'&')
+        .find_map(|pair| pair.strip_prefix(b"q="))
+        .map(percent_decode)
+        .unwrap_or_default();
+
+    let mut list = HtmlList::default();
+    let matcher = Literal::new(&query, false);
+    Searcher::new().search_path(&matcher, Path::new(SEARCH_FILE), &mut list)?;
+    Ok(format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<body>\n<p>{} line(s) matching \"{}\" in {}</p>\n<ul>\n{}</ul>\n</body>\n</html>\n",
+        list.count,
+        escape_html(&query),
+        SEARCH_FILE,
+        list.items
+    ))
+}
+
+/// 解码查询字符串里的 `%XX` 和 `+`
+fn percent_decode(bytes: &[u8]) -> String {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        // `from_str_radix` 接受开头的 `+`，所以先检查两个字符都是十六进制数字
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .filter(|h| h.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
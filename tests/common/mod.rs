@@ -1,2 +1,19 @@
 // NOTE: rust 不会把子目录里的文件视为集成测试文件
+// 每个测试文件只用到其中一部分函数
+#![allow(dead_code)]
+
+use std::{fs, path::PathBuf};
+
 pub fn setup() {}
+
+/// 在系统临时目录下创建一个空目录，`files` 为 (相对路径, 内容)
+pub fn temp_tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("learning_rust-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    for (path, contents) in files {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+    root
+}
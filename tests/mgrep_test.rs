@@ -1,4 +1,24 @@
-use learning_rust::mgrep::{self};
+mod common;
+
+use learning_rust::mgrep::{
+    self,
+    aho_corasick::AhoCorasick,
+    args::{ColorChoice, Config, ConfigError},
+    color::{Colors, Style},
+    filter::Filter,
+    fuzzy::Fuzzy,
+    glob::Glob,
+    gzip::{self, GzDecoder},
+    json,
+    matcher::{self, Literal, Matcher, WordMatcher},
+    memchr::{self, Finder},
+    printer::Printer,
+    regex::{self, Regex},
+    replace::Replacer,
+    searcher::{self, Searcher, Sink, Stats},
+    unicode,
+    walk::Walk,
+};
 
 #[test]
 fn it_one_result() {
@@ -40,6 +60,1151 @@ Duct tape.";
 
     assert_eq!(
         vec!["safe, fast, productive.", "Duct tape."],
-        mgrep::search(query, contents)
+        mgrep::search_case_insensitive(query, contents)
+    );
+}
+
+#[test]
+fn it_regex_class_and_anchor() {
+    let re = Regex::new(r"^[A-Z]\w+ (you|us)\b").unwrap();
+    let contents = "\
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+Are you nobody, too?
+Trust us.";
+
+    assert_eq!(
+        vec!["Are you nobody, too?", "Trust us."],
+        mgrep::search_regex(&re, contents)
+    );
+}
+
+#[test]
+fn it_regex_repetition() {
+    let re = Regex::new(r"a{2,3}b+?").unwrap();
+    assert_eq!(Some((1, 5)), re.find("caaab"));
+    assert_eq!(Some((0, 3)), re.find("aabbb"));
+    assert!(!re.is_match("abab"));
+
+    let lazy = Regex::new(r"<.+?>").unwrap();
+    assert_eq!(Some((0, 3)), lazy.find("<a><b>"));
+
+    // 几万条连续的 Split/Save，不消耗字符就能走完
+    let long = Regex::new(r"((?:a?){1000}){30}b").unwrap();
+    assert_eq!(Some((0, 3)), long.find("aab"));
+}
+
+#[test]
+fn it_regex_captures() {
+    let re = Regex::new(r#"(?P<key>[a-z_]+)\s*=\s*(\d+|"[^"]*")"#).unwrap();
+    let caps = re.captures("  opt_level = 3").unwrap();
+    assert_eq!(Some("opt_level = 3"), caps.get(0));
+    assert_eq!(Some("opt_level"), caps.name("key"));
+    assert_eq!(Some("3"), caps.get(2));
+    assert_eq!(3, caps.len());
+}
+
+#[test]
+fn it_regex_case_insensitive() {
+    let re = Regex::new("(?i)rust|duct").unwrap();
+    assert!(re.is_match("Trust"));
+    assert!(re.is_match("DUCT tape"));
+    assert!(Regex::new_case_insensitive("[a-c]+")
+        .unwrap()
+        .is_match("ABC"));
+}
+
+#[test]
+fn it_regex_inline_flags() {
+    let re = Regex::new("a(?i)b").unwrap();
+    assert!(re.is_match("aB"));
+    assert!(!re.is_match("AB"));
+
+    let re = Regex::new("x(?i:y)z").unwrap();
+    assert!(re.is_match("xYz"));
+    assert!(!re.is_match("xYZ"));
+
+    // `(?i)` 只作用到所在的组结束
+    let re = Regex::new("(a(?i)b)c").unwrap();
+    assert!(re.is_match("aBc"));
+    assert!(!re.is_match("aBC"));
+
+    let re = Regex::new_case_insensitive("a(?-i)b").unwrap();
+    assert!(re.is_match("Ab"));
+    assert!(!re.is_match("AB"));
+
+    assert_eq!(
+        regex::ErrorKind::InvalidFlag,
+        Regex::new("(?x)a").unwrap_err().kind
+    );
+}
+
+#[test]
+fn it_regex_errors() {
+    assert_eq!(
+        regex::ErrorKind::UnclosedGroup,
+        Regex::new("(ab").unwrap_err().kind
+    );
+    assert_eq!(
+        regex::ErrorKind::NothingToRepeat,
+        Regex::new("*a").unwrap_err().kind
+    );
+    assert_eq!(
+        regex::ErrorKind::UnclosedClass,
+        Regex::new("[a-").unwrap_err().kind
+    );
+    assert_eq!(
+        regex::ErrorKind::RepeatTooLarge,
+        Regex::new("((a{1000}){1000}){1000}").unwrap_err().kind
+    );
+    assert!(Regex::new("(a{1000}){50}").is_ok());
+}
+
+#[test]
+fn it_glob() {
+    assert!(Glob::new("*.rs").is_match("lib.rs"));
+    assert!(!Glob::new("*.rs").is_match("src/lib.rs"));
+    assert!(Glob::new("src/**/*.rs").is_match("src/lib.rs"));
+    assert!(Glob::new("src/**/*.rs").is_match("src/mgrep/walk.rs"));
+    assert!(Glob::new("target/**").is_match("target/debug/demo"));
+    assert!(Glob::new("[!a-c]?.txt").is_match("d1.txt"));
+    assert!(!Glob::new("[!a-c]?.txt").is_match("b1.txt"));
+
+    // 很多个 `*` 也不会回溯成指数级
+    let long = "a".repeat(60);
+    assert!(!Glob::new(&"*a".repeat(12)).is_match(&(long.clone() + "b")));
+    assert!(!Glob::new(&"**/a".repeat(12)).is_match(&"a/".repeat(30)));
+    assert!(Glob::new(&"*a".repeat(12)).is_match(&long));
+}
+
+#[test]
+fn it_walk_honors_ignore_files() {
+    let root = common::temp_tree(
+        "walk",
+        &[
+            (".gitignore", "*.log\nbuild/\n/top.txt\n"),
+            ("a.txt", ""),
+            ("top.txt", ""),
+            ("debug.log", ""),
+            ("build/out.txt", ""),
+            ("sub/top.txt", ""),
+            ("sub/.ignore", "!keep.log\n"),
+            ("sub/keep.log", ""),
+            ("sub/drop.log", ""),
+            (".git/config", ""),
+        ],
+    );
+
+    let files: Vec<String> = Walk::new(&root)
+        .map(|p| {
+            let p = p.unwrap();
+            p.strip_prefix(&root)
+                .unwrap()
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+
+    assert_eq!(
+        vec![
+            ".gitignore",
+            "a.txt",
+            "sub/.ignore",
+            "sub/keep.log",
+            "sub/top.txt"
+        ],
+        files
+    );
+}
+
+fn args(list: &[&str]) -> Vec<String> {
+    std::iter::once("minigrep")
+        .chain(list.iter().copied())
+        .map(String::from)
+        .collect()
+}
+
+#[test]
+fn test_parse_config() {
+    let config = Config::new(args(&["-in", "--color=never", "-E", "you", "a.txt", "src"])).unwrap();
+    assert_eq!(vec!["you"], config.patterns);
+    assert_eq!(2, config.paths.len());
+    assert!(!config.case_sensitive);
+    assert!(config.line_number && config.regex);
+    assert!(!config.count && !config.invert_match);
+    assert_eq!(ColorChoice::Never, config.color);
+
+    let config = Config::new(args(&["-v", "--", "-w", "a.txt"])).unwrap();
+    assert_eq!(vec!["-w"], config.patterns);
+    assert!(config.invert_match && !config.word);
+}
+
+#[test]
+fn test_parse_config_errors() {
+    assert_eq!(
+        ConfigError::MissingQuery,
+        Config::new(args(&["-n"])).unwrap_err()
+    );
+    assert!(Config::new(args(&["you"])).unwrap().paths.is_empty());
+    assert_eq!(
+        ConfigError::UnknownOption(String::from("-x")),
+        Config::new(args(&["-nx", "you", "a.txt"])).unwrap_err()
+    );
+    assert_eq!(
+        ConfigError::InvalidValue {
+            option: String::from("--color"),
+            value: String::from("sometimes"),
+        },
+        Config::new(args(&["--color=sometimes", "you", "a.txt"])).unwrap_err()
+    );
+    assert!(Config::new(args(&["--help"])).unwrap_err().is_info());
+}
+
+#[test]
+fn it_find_matches_with_spans() {
+    let contents = "Rust:\r\nsafe, fast, productive.\nPick three.\nDuct tape, duct tape.";
+    let matcher = Literal::new("duct", false);
+
+    let matches = mgrep::find_matches(&matcher, contents);
+    assert_eq!(2, matches.len());
+
+    assert_eq!(2, matches[0].line_number);
+    assert_eq!(7, matches[0].byte_offset);
+    assert_eq!("safe, fast, productive.", matches[0].line);
+    assert_eq!(vec![(15, 19)], matches[0].spans);
+    assert_eq!(16, matches[0].column());
+
+    assert_eq!(4, matches[1].line_number);
+    assert_eq!(vec![(0, 4), (11, 15)], matches[1].spans);
+
+    let lines: Vec<usize> = mgrep::find_non_matches(&matcher, contents)
+        .iter()
+        .map(|m| m.line_number)
+        .collect();
+    assert_eq!(vec![1, 3], lines);
+}
+
+#[test]
+fn it_find_iter_keeps_byte_offsets() {
+    let matcher = Literal::new("straße", false);
+    assert_eq!(vec![(4, 11)], matcher.find_iter("Die STRAßE"));
+
+    let re = Regex::new("x*").unwrap();
+    assert_eq!(vec![(0, 0), (1, 3), (3, 3)], re.find_iter("axx"));
+}
+
+fn print_context(list: &[&str], contents: &str) -> String {
+    let config = Config::new(args(list)).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    let mut out = Vec::new();
+    let mut printer = Printer::new(&mut out, &config, false);
+    searcher::search_reader(&config, matcher.as_ref(), contents.as_bytes(), &mut printer).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn it_context_windows_merge() {
+    let contents = "a\nb\nmatch 1\nc\nmatch 2\nd\ne\nf\ng\nmatch 3\nh";
+
+    assert_eq!(
+        "2-b\n3:match 1\n4-c\n5:match 2\n6-d\n--\n9-g\n10:match 3\n11-h\n",
+        print_context(&["--color=never", "-n", "-C", "1", "match", "x"], contents)
+    );
+    assert_eq!(
+        "3:match 1\n4-c\n5:match 2\n6-d\n7-e\n--\n10:match 3\n11-h\n",
+        print_context(&["--color=never", "-nA2", "match", "x"], contents)
+    );
+    assert_eq!(
+        "1-a\n2-b\n3:match 1\n4-c\n5:match 2\n",
+        print_context(
+            &["--color=never", "-n", "-C3", "-A0", "match 1|2", "-E", "x"],
+            contents
+        )
+    );
+}
+
+#[test]
+fn it_streams_over_invalid_utf8() {
+    let config = Config::new(args(&["--color=never", "-n", "you"])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    let input: &[u8] = b"ok you\n\xff\xfe you\r\nyou again";
+    let mut out = Vec::new();
+    let mut printer = Printer::new(&mut out, &config, false);
+
+    let stats = searcher::search_reader(&config, matcher.as_ref(), input, &mut printer).unwrap();
+    assert_eq!(
+        Stats {
+            lines: 3,
+            selected: 2,
+            invalid_utf8: 1,
+            binary: false,
+        },
+        stats
+    );
+    assert_eq!("1:ok you\n3:you again\n", String::from_utf8(out).unwrap());
+
+    // 不合法的行也算在上下文窗口里
+    let config = Config::new(args(&["--color=never", "-n", "-C1", "you"])).unwrap();
+    let input: &[u8] = b"you 1\n\xff\nnot after\nnot before\n\xfe\nyou 2\n";
+    let mut out = Vec::new();
+    let mut printer = Printer::new(&mut out, &config, false);
+    searcher::search_reader(&config, matcher.as_ref(), input, &mut printer).unwrap();
+    assert_eq!("1:you 1\n--\n6:you 2\n", String::from_utf8(out).unwrap());
+
+    let binary: &[u8] = b"you\0";
+    let mut printer = Printer::new(Vec::new(), &config, false);
+    let stats = searcher::search_reader(&config, matcher.as_ref(), binary, &mut printer).unwrap();
+    assert!(stats.binary);
+}
+
+#[test]
+fn it_parses_grep_colors() {
+    let colors = Colors::parse("ms=04;38;2;255;0;0:fn=blue:se=:ln=bogus:xx=1");
+    assert_eq!(
+        Style {
+            underline: true,
+            ..Style::fg(colored::Color::TrueColor { r: 255, g: 0, b: 0 })
+        },
+        colors.matched
+    );
+    assert_eq!(Style::fg(colored::Color::Blue), colors.filename);
+    assert_eq!(Style::default(), colors.separator);
+    assert_eq!(Colors::default().line_number, colors.line_number);
+    assert_eq!(None, Style::parse("01;999"));
+}
+
+#[test]
+fn it_highlights_matches() {
+    let config = Config::new(args(&["--color=always", "-n", "you"])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    let mut out = Vec::new();
+    let mut printer = Printer::new(&mut out, &config, false).colors(Colors::parse("ms=7:ln=:se="));
+    colored::control::set_override(true);
+    searcher::search_reader(&config, matcher.as_ref(), &b"you or you"[..], &mut printer).unwrap();
+    colored::control::unset_override();
+
+    assert_eq!(
+        "1:\u{1b}[7myou\u{1b}[0m or \u{1b}[7myou\u{1b}[0m\n",
+        String::from_utf8(out).unwrap()
+    );
+}
+
+#[test]
+fn it_parallel_output_is_ordered() {
+    let files: Vec<(String, String)> = (0..40)
+        .map(|i| {
+            let body = (0..200)
+                .map(|n| format!("line {} of file {}\n", n, i))
+                .collect();
+            (format!("dir{}/file{:02}.txt", i % 3, i), body)
+        })
+        .collect();
+    let files: Vec<(&str, &str)> = files
+        .iter()
+        .map(|(p, b)| (p.as_str(), b.as_str()))
+        .collect();
+    let root = common::temp_tree("parallel", &files);
+
+    let grep = |threads: &str| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(["--color=never", "-n", "-j", threads, "-E", "line 1?7 "])
+            .arg(&root)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let sequential = grep("1");
+    assert_eq!(40 * 2, sequential.lines().count());
+    assert_eq!(sequential, grep("8"));
+}
+
+#[test]
+fn it_selects_files_and_exits_like_grep() {
+    let root = common::temp_tree(
+        "exit_codes",
+        &[
+            ("a.txt", "foo 1\nbar\nfoo 2\nfoo 3\nbaz\n"),
+            ("b.txt", "nothing here\n"),
+        ],
+    );
+    let grep = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .arg("--color=never")
+            .args(args)
+            .current_dir(&root)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.code().unwrap(), stdout)
+    };
+
+    assert_eq!(
+        (0, String::from("match: foo 1\n")),
+        grep(&["-m1", "foo", "a.txt"])
+    );
+    assert_eq!(
+        (0, String::from("match: foo 1\n       bar\n")),
+        grep(&["-m1", "-A1", "foo", "a.txt"])
+    );
+    assert_eq!(
+        (0, String::from("2\n")),
+        grep(&["-c", "-m2", "foo", "a.txt"])
+    );
+    assert_eq!(
+        (0, String::from("a.txt\n")),
+        grep(&["-l", "foo", "a.txt", "b.txt"])
+    );
+    assert_eq!(
+        (0, String::from("b.txt\n")),
+        grep(&["-L", "foo", "a.txt", "b.txt"])
+    );
+    assert_eq!((1, String::new()), grep(&["-L", "foo", "a.txt"]));
+    assert_eq!((1, String::new()), grep(&["missing", "a.txt"]));
+    assert_eq!((0, String::new()), grep(&["-q", "foo", "b.txt", "a.txt"]));
+    assert_eq!((1, String::new()), grep(&["-q", "foo", "b.txt"]));
+    assert_eq!((2, String::new()), grep(&["foo", "nope.txt"]));
+    // `-q` 时只要有匹配，其他文件的错误不影响退出码
+    assert_eq!(
+        (0, String::new()),
+        grep(&["-q", "foo", "nope.txt", "a.txt"])
+    );
+    assert_eq!(2, grep(&["--max-count=x", "foo"]).0);
+}
+
+#[test]
+fn it_case_folds_unicode() {
+    let contents = "\
+Die Straße ist lang.
+DIE STRAẞE IST LANG.
+die strasse ist lang.
+ΣΟΦΟΣ σοφος
+İstanbul
+istanbul";
+
+    assert_eq!(
+        vec!["Die Straße ist lang.", "DIE STRAẞE IST LANG."],
+        mgrep::search_case_insensitive("straße", contents)
+    );
+    assert_eq!(
+        vec!["ΣΟΦΟΣ σοφος"],
+        mgrep::search_case_insensitive("σοφοσ", contents)
+    );
+    assert_eq!(
+        vec!["istanbul"],
+        mgrep::search_case_insensitive("ISTANBUL", contents)
+    );
+    assert_eq!(
+        vec!["İstanbul"],
+        mgrep::search_case_insensitive("İSTANBUL", contents)
+    );
+
+    assert_eq!('s', unicode::simple_fold('ſ'));
+    assert_eq!('ß', unicode::simple_fold('ẞ'));
+    assert_eq!('İ', unicode::simple_fold('İ'));
+    assert!(Regex::new_case_insensitive("strasse|σοφος")
+        .unwrap()
+        .is_match("ΣΟΦΟΣ"));
+}
+
+#[test]
+fn it_matches_whole_words() {
+    let word = WordMatcher::new(Literal::new("cafe", false));
+    assert_eq!(
+        vec![(0, 4), (27, 31)],
+        word.find_iter("CAFE cafeteria café cafe_ cafe")
+    );
+    assert!(!word.is_match("cafe\u{301}"));
+    assert!(!word.is_match("日本cafe"));
+    assert!(word.is_match("«cafe»"));
+
+    // 第一个候选不满足条件时继续往后找
+    let word = WordMatcher::new(Regex::new(r"\d+").unwrap());
+    assert_eq!(Some((7, 9)), word.find_at("abc123 45", 0));
+
+    let config = Config::new(args(&["-w", "-i", "über"])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    assert!(matcher.is_match("ÜBER alles"));
+    assert!(!matcher.is_match("überall"));
+}
+
+/// 逐个模式查找，取最左最长的匹配
+fn naive_find(patterns: &[&str], line: &str) -> Option<(usize, usize)> {
+    patterns
+        .iter()
+        .filter_map(|p| line.find(p).map(|s| (s, s + p.len())))
+        .min_by_key(|&(s, e)| (s, std::cmp::Reverse(e)))
+}
+
+#[test]
+fn it_matches_many_patterns_in_one_pass() {
+    let patterns = ["he", "she", "his", "hers", "s", "ushe"];
+    let ac = AhoCorasick::new(patterns, true);
+    for line in ["ushers", "this is hers", "ahishers", "nothing", "", "sss"] {
+        assert_eq!(naive_find(&patterns, line), ac.find_at(line, 0), "{}", line);
+    }
+    assert_eq!(
+        vec![(0, 4), (5, 7), (7, 8), (8, 9)],
+        ac.find_iter("ushe hess")
+    );
+
+    // 忽略大小写按字符折叠，字节区间对应原文
+    let ac = AhoCorasick::new(["straße", "σοφος"], false);
+    assert_eq!(Some((4, 12)), ac.find_at("Die STRAẞE", 0));
+    assert_eq!(Some((0, 10)), ac.find_at("ΣΟΦΟΣ", 0));
+
+    // 空模式匹配每一行，没有模式什么都不匹配
+    assert_eq!(
+        Some((0, 0)),
+        AhoCorasick::new(["", "x"], true).find_at("ab", 0)
+    );
+    assert_eq!(
+        Some((0, 1)),
+        AhoCorasick::new(["", "x"], true).find_at("xb", 0)
+    );
+    assert!(!AhoCorasick::new(Vec::<&str>::new(), true).is_match("anything"));
+}
+
+#[test]
+fn it_reads_patterns_from_flags_and_files() {
+    let root = common::temp_tree(
+        "pattern_file",
+        &[
+            ("blocklist.txt", "rust\r\nUnsafe\n"),
+            (
+                "code.rs",
+                "fn main() {}\nunsafe { rust }\n// trusty\nlet x = 1;\n",
+            ),
+        ],
+    );
+    let blocklist = root.join("blocklist.txt").display().to_string();
+    let code = root.join("code.rs").display().to_string();
+
+    let config = Config::new(args(&["-e", "a\nb", "-e", "c", "-f", &blocklist, &code])).unwrap();
+    assert_eq!(vec!["a", "b", "c", "rust", "Unsafe"], config.patterns);
+    assert_eq!(1, config.paths.len());
+
+    let config = Config::new(args(&["-i", "-f", &blocklist, &code])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    assert_eq!(vec![(0, 6), (9, 13)], matcher.find_iter("unsafe { rust }"));
+    assert!(matcher.is_match("// trusty"));
+    assert!(!matcher.is_match("let x = 1;"));
+
+    let config = Config::new(args(&["-E", "-e", r"^\w+\(", "-e", "[0-9]+;$", &code])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    assert!(matcher.is_match("let x = 1;"));
+    assert!(!matcher.is_match("fn main() {}"));
+
+    // 每个模式单独报告错误位置
+    let config = Config::new(args(&["-E", "-e", "ok", "-e", "a)|(b"])).unwrap();
+    assert_eq!(1, matcher::from_config(&config).err().unwrap().pos);
+
+    assert!(matches!(
+        Config::new(args(&["-f", "no/such/file", "x"])),
+        Err(ConfigError::PatternFile { .. })
+    ));
+}
+
+#[test]
+fn it_finds_bytes_and_substrings_like_std() {
+    let text =
+        "lorem ipsum dolor sit amet, consectetur adipiscing elit\n".repeat(5) + "ünïcödé zzz";
+    for b in [b'l', b'\n', b't', b'z', b'Q', 0xA9] {
+        let bytes = text.as_bytes();
+        assert_eq!(bytes.iter().position(|&x| x == b), memchr::memchr(b, bytes));
+        assert_eq!(
+            bytes.iter().rposition(|&x| x == b),
+            memchr::memrchr(b, bytes)
+        );
+    }
+
+    let long = "a".repeat(300) + "ab" + &"ba".repeat(100);
+    for (needle, haystack) in [
+        ("elit\nlorem", text.as_str()),
+        ("cödé", text.as_str()),
+        ("zzz", text.as_str()),
+        ("zzzz", text.as_str()),
+        ("or", text.as_str()),
+        ("", text.as_str()),
+        ("aaab", long.as_str()),
+        ("abba", long.as_str()),
+        ("bab", long.as_str()),
+    ] {
+        let finder = Finder::new(needle);
+        for start in [0, 1, 7, 57, 250, 301] {
+            let start = (start.min(haystack.len())..=haystack.len())
+                .find(|&i| haystack.is_char_boundary(i))
+                .unwrap();
+            let expected = haystack[start..].find(needle).map(|i| start + i);
+            assert_eq!(
+                expected,
+                finder.find_at(haystack, start),
+                "{:?} from {}",
+                needle,
+                start
+            );
+        }
+    }
+}
+
+#[test]
+fn it_searches_whole_buffer_like_lines() {
+    let contents = "duct tape\r\nno\nproductive\r\n\nduct\rtape\nlast duct";
+    for query in [
+        "duct",
+        "t",
+        "duct\r",
+        "e\r",
+        "\r",
+        "no\nproductive",
+        "",
+        "missing",
+    ] {
+        let expected: Vec<&str> = contents.lines().filter(|x| x.contains(query)).collect();
+        assert_eq!(expected, mgrep::search(query, contents), "{:?}", query);
+    }
+}
+
+#[test]
+fn it_expands_replacement_templates() {
+    let re = Regex::new(r"(?P<name>\w+)@(\w+)\.com").unwrap();
+    let line = "mail ada@example.com or bob@test.com";
+    let replace = |template: &str| Replacer::new(template).replace(&re, line).unwrap().0;
+    assert_eq!("mail example:ada or test:bob", replace("$2:${name}"));
+    assert_eq!("mail $ada$ or $bob$", replace("$$$1$$"));
+    assert_eq!("mail [] or []", replace("[$9${nope}]"));
+    assert_eq!("mail <ada@example.com> or <bob@test.com>", replace("<$0>"));
+    assert_eq!("mail $ or $", replace("$"));
+    assert_eq!("mail ${name or ${name", replace("${name"));
+
+    // 普通字符串模式下只有 `$0`，`-w` 时捕获组也要满足单词边界
+    let literal = WordMatcher::new(Literal::new("cat", false));
+    assert_eq!(
+        Some((String::from("[Cat] concat [cat]"), vec![(0, 5), (13, 18)])),
+        Replacer::new("[$0$1]").replace(&literal, "Cat concat cat")
+    );
+    assert_eq!(None, Replacer::new("x").replace(&literal, "concat"));
+}
+
+#[test]
+fn it_keeps_flags_and_groups_per_pattern() {
+    let config = Config::new(args(&["-E", "-e", "(?i)foo", "-e", "bar"])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    assert!(matcher.is_match("FOO"));
+    assert!(!matcher.is_match("BAR"));
+
+    // 多个 `-e` 时 `$1` 引用的是匹配到的那个模式自己的第 1 组
+    let config = Config::new(args(&["-E", "-e", r"(\d+)px", "-e", r"(\w)(\d+)em"])).unwrap();
+    let matcher = matcher::from_config(&config).unwrap();
+    let (line, _) = Replacer::new("<$1/$2>")
+        .replace(&matcher, "12px and x3em")
+        .unwrap();
+    assert_eq!("<12/> and <x/3>", line);
+}
+
+#[test]
+fn it_replaces_in_place_and_prints_diffs() {
+    let root = common::temp_tree(
+        "replace",
+        &[
+            (
+                "a.txt",
+                "let foo = 1;\r\nfoo(foo);\n1\n2\n3\n4\n5\n6\n7\nlast foo",
+            ),
+            ("b.txt", "nothing here\n"),
+        ],
+    );
+    let grep = |args: &[&str]| {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .arg("--color=never")
+            .args(args)
+            .current_dir(&root)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        (output.status.code().unwrap(), stdout)
+    };
+    let original = std::fs::read_to_string(root.join("a.txt")).unwrap();
+
+    assert_eq!(
+        (0, String::from("1:let bar = 1;\n2:bar(bar);\n")),
+        grep(&["-n", "-m2", "-r", "bar", "foo", "a.txt"])
+    );
+    assert_eq!(
+        (0, String::from("a.txt:let_foo = 1;\na.txt:last_foo\n")),
+        grep(&[
+            "-E",
+            "-r",
+            "${v}_$2",
+            r"(?P<v>\w)\s(f\w+)",
+            "a.txt",
+            "b.txt"
+        ])
+    );
+
+    let diff = "\
+--- a/./a.txt
++++ b/./a.txt
+@@ -1,5 +1,5 @@
+-let foo = 1;\r
+-foo(foo);
++let [foo] = 1;\r
++[foo]([foo]);
+ 1
+ 2
+ 3
+@@ -7,4 +7,4 @@
+ 5
+ 6
+ 7
+-last foo
+\\ No newline at end of file
++last [foo]
+\\ No newline at end of file
+";
+    assert_eq!(
+        (0, String::from(diff)),
+        grep(&["--dry-run", "-r", "[$0]", "foo", "."])
+    );
+    assert_eq!(
+        original,
+        std::fs::read_to_string(root.join("a.txt")).unwrap()
+    );
+
+    assert_eq!(
+        (0, String::new()),
+        grep(&["--in-place", "-r", "[$0]", "foo", "."])
+    );
+    assert_eq!(
+        original.replace("foo", "[foo]"),
+        std::fs::read_to_string(root.join("a.txt")).unwrap()
+    );
+    assert_eq!(1, grep(&["--in-place", "-r", "x", "missing", "."]).0);
+    assert_eq!(2, grep(&["--in-place", "-r", "x", "foo"]).0);
+    assert_eq!(2, grep(&["--dry-run", "foo", "a.txt"]).0);
+    // 临时文件都已经改名或者删除
+    let names: Vec<_> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(2, names.len());
+}
+
+#[test]
+fn it_emits_json_lines() {
+    assert_eq!(
+        r#""say \"hi\"\t\\ \u0001 ü""#,
+        json::string("say \"hi\"\t\\ \u{1} ü")
+    );
+    assert_eq!(
+        r#"{"a":1,"b":"x","c":[true,false]}"#,
+        json::Object::new()
+            .value("a", 1)
+            .string("b", "x")
+            .raw("c", &json::array([true, false].map(|b| b.to_string())))
+            .finish()
+    );
+
+    let root = common::temp_tree(
+        "json",
+        &[("a.txt", "one\nfoo bar foo\ntwo\n"), ("b.txt", "nothing\n")],
+    );
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["--json", "-A1", "foo", "a.txt", "b.txt"])
+        .current_dir(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        vec![
+            r#"{"type":"begin","path":"a.txt"}"#,
+            r#"{"type":"match","path":"a.txt","line_number":2,"byte_offset":4,"line":"foo bar foo","submatches":[{"text":"foo","start":0,"end":3},{"text":"foo","start":8,"end":11}]}"#,
+            r#"{"type":"context","path":"a.txt","line_number":3,"byte_offset":16,"line":"two"}"#,
+            r#"{"type":"end","path":"a.txt","stats":{"lines":3,"selected":1,"invalid_utf8":0,"binary":false}}"#,
+        ],
+        lines[..4]
+    );
+    // 耗时每次都不一样，只比较前面的部分
+    assert!(lines[4].starts_with(
+        r#"{"type":"summary","stats":{"files":2,"files_matched":1,"selected":1,"errors":0,"elapsed_ms":"#
+    ));
+    assert_eq!(5, lines.len());
+
+    assert_eq!(
+        ConfigError::Conflicts(String::from("--json"), String::from("--count")),
+        Config::new(args(&["--json", "-c", "foo"])).unwrap_err()
+    );
+}
+
+/// 普通 DP 算的最小子串编辑距离，用来核对位并行的结果
+fn naive_distance(pattern: &str, line: &str) -> usize {
+    let p: Vec<char> = pattern.chars().collect();
+    let mut column: Vec<usize> = (0..=p.len()).collect();
+    let mut best = p.len();
+    for c in line.chars() {
+        let mut diagonal = column[0];
+        for i in 1..=p.len() {
+            let next = (diagonal + usize::from(p[i - 1] != c))
+                .min(column[i] + 1)
+                .min(column[i - 1] + 1);
+            diagonal = column[i];
+            column[i] = next;
+        }
+        best = best.min(column[p.len()]);
+    }
+    best
+}
+
+#[test]
+fn it_matches_within_edit_distance() {
+    let fuzzy = Fuzzy::new("timeout", 2, true);
+    assert_eq!(Some(0), fuzzy.distance("read timeout after 3s"));
+    assert_eq!(Some(1), fuzzy.distance("read timout after 3s"));
+    assert_eq!(Some(2), fuzzy.distance("read tiemout after 3s"));
+    assert_eq!(None, fuzzy.distance("read tmieuot after 3s"));
+    assert_eq!(Some((5, 11)), fuzzy.find_at("read timout after 3s", 0));
+    assert_eq!(
+        vec![(0, 7), (12, 18)],
+        fuzzy.find_iter("timeout and tmeout")
+    );
+
+    // 忽略大小写按字符比较，区间是原文的字节偏移
+    let fuzzy = Fuzzy::new("straße", 1, false);
+    assert_eq!(Some(1), fuzzy.distance("Die STRASE"));
+    assert_eq!(Some((4, 10)), fuzzy.find_at("Die STRASE", 0));
+    assert_eq!(Some((0, 8)), fuzzy.find_at("STRAẞE!", 0));
+
+    // 超过 64 个字符的 query 走普通 DP，结果要和位并行一致
+    let line = "x".repeat(30) + &"abcdefghij".repeat(7) + "yyy";
+    for (query, k) in [
+        ("abcdefghij".repeat(7).replacen('c', "Q", 1), 3),
+        ("abcdefghij".repeat(6), 0),
+        ("abcdefghij".repeat(6).replacen('d', "", 2), 2),
+    ] {
+        let expected = naive_distance(&query, &line);
+        assert_eq!(
+            (expected <= k).then_some(expected),
+            Fuzzy::new(&query, k, true).distance(&line),
+            "{}",
+            query
+        );
+    }
+    for line in [
+        "conection",
+        "cnnection refused",
+        "connectoin",
+        "xconnectionx",
+        "",
+    ] {
+        let expected = naive_distance("connection", line);
+        assert_eq!(
+            Some(expected),
+            Fuzzy::new("connection", 10, true).distance(line)
+        );
+    }
+}
+
+#[test]
+fn it_ranks_fuzzy_matches_by_distance() {
+    let contents = "conection refused\nconnection refused\nunrelated\ncnnectoin\nconnection reset";
+    assert_eq!(
+        "2:connection refused\n5:connection reset\n1:conection refused\n",
+        print_context(
+            &["--color=never", "-n", "-C1", "--fuzzy=2", "connection"],
+            contents
+        )
+    );
+    assert_eq!(
+        "3\n",
+        print_context(&["--fuzzy", "1", "-c", "connection"], contents)
+    );
+    assert_eq!(
+        ConfigError::Conflicts(String::from("--fuzzy"), String::from("--regex")),
+        Config::new(args(&["--fuzzy=1", "-E", "x"])).unwrap_err()
+    );
+}
+
+/// `tests/data` 下的测试文件，`.gz` 都是用系统的 gzip 生成的
+fn data(name: &str) -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+#[test]
+fn it_decompresses_gzip_members() {
+    use std::io::Read;
+
+    let read = |bytes: &[u8]| {
+        let mut text = String::new();
+        GzDecoder::new(bytes)
+            .read_to_string(&mut text)
+            .map(|_| text)
+    };
+
+    // 动态 Huffman 块，解压结果远大于 32KB 的窗口
+    let log = read(&std::fs::read(data("app.log.gz")).unwrap()).unwrap();
+    assert_eq!(4000, log.lines().count());
+    assert_eq!(
+        Some("2024-01-01T00:16:40 ERROR request 1000 served in 107 ms"),
+        log.lines().nth(999)
+    );
+
+    let multi = std::fs::read(data("multi.gz")).unwrap();
+    assert_eq!("first member\nsecond member\n", read(&multi).unwrap());
+    // 存储块
+    let stored = std::fs::read(data("stored.txt.gz")).unwrap();
+    assert_eq!("stored block\nneedle here\n", read(&stored).unwrap());
+
+    let mut corrupt = multi.clone();
+    let trailer = multi.len() / 2 - 8;
+    corrupt[trailer] ^= 1;
+    assert_eq!(
+        std::io::ErrorKind::InvalidData,
+        read(&corrupt).unwrap_err().kind()
+    );
+    assert_eq!(
+        std::io::ErrorKind::UnexpectedEof,
+        read(&multi[..multi.len() - 3]).unwrap_err().kind()
+    );
+    assert!(!gzip::is_gzip(b"plain text"));
+}
+
+#[test]
+fn it_searches_gzip_files() {
+    let grep = |args: &[&str], stdin: Option<&str>| {
+        let mut command = std::process::Command::new(env!("CARGO_BIN_EXE_minigrep"));
+        command
+            .arg("--color=never")
+            .args(args)
+            .current_dir(data(""))
+            .stdout(std::process::Stdio::piped());
+        if let Some(name) = stdin {
+            command.stdin(std::fs::File::open(data(name)).unwrap());
+        }
+        let output = command.output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(
+        "1000:2024-01-01T00:16:40 ERROR request 1000 served in 107 ms\n\
+         4000:2024-01-01T00:06:40 ERROR request 4000 served in 89 ms\n",
+        grep(&["-n", "-E", "ERROR.* [14]000 ", "app.log.gz"], None)
+    );
+    assert_eq!(
+        "match: second member\n",
+        grep(&["second", "multi.gz"], None)
+    );
+    assert_eq!("35\n", grep(&["-c", "served in 0 ms"], Some("app.log.gz")));
+}
+
+/// 记下 `Searcher` 的每次回调，`limit` 条匹配以后要求停止
+#[derive(Default)]
+struct Events {
+    events: Vec<String>,
+    progress: Vec<usize>,
+    limit: usize,
+}
+
+impl Sink for Events {
+    fn matched(&mut self, m: &mgrep::Match) -> std::io::Result<bool> {
+        self.events.push(format!("{}:{}", m.line_number, m.line));
+        Ok(self.events.iter().filter(|e| e.contains(':')).count() < self.limit)
+    }
+
+    fn context(&mut self, line_number: usize, _: usize, line: &str) -> std::io::Result<bool> {
+        self.events.push(format!("{}-{}", line_number, line));
+        Ok(true)
+    }
+
+    fn invalid_utf8(&mut self, line_number: usize, _: usize) -> std::io::Result<()> {
+        self.events.push(format!("{}!", line_number));
+        Ok(())
+    }
+
+    fn progress(&mut self, stats: &Stats) -> std::io::Result<bool> {
+        self.progress.push(stats.lines);
+        Ok(true)
+    }
+}
+
+#[test]
+fn it_reports_to_a_sink() {
+    let matcher = Literal::new("x", true);
+    let input: &[u8] = b"a\nx1\nb\n\xff\nc\nx2\nd\nx3\ne";
+
+    let mut sink = Events {
+        limit: usize::MAX,
+        ..Events::default()
+    };
+    let stats = Searcher::new()
+        .context(1, 0)
+        .search_reader(&matcher, input, &mut sink)
+        .unwrap();
+    assert_eq!(
+        vec!["1-a", "2:x1", "4!", "5-c", "6:x2", "7-d", "8:x3"],
+        sink.events
+    );
+    assert_eq!((9, 3, 1), (stats.lines, stats.selected, stats.invalid_utf8));
+
+    // `Sink` 返回 `false` 以后不再读后面的行
+    let mut sink = Events {
+        limit: 2,
+        ..Events::default()
+    };
+    let stats = Searcher::new()
+        .search_reader(&matcher, input, &mut sink)
+        .unwrap();
+    assert_eq!(vec!["2:x1", "4!", "6:x2"], sink.events);
+    assert_eq!(6, stats.lines);
+
+    let stats = Searcher::new()
+        .invert_match(true)
+        .count_only(true)
+        .search_reader(&matcher, input, &mut sink)
+        .unwrap();
+    assert_eq!(5, stats.selected);
+
+    let big = "line\n".repeat(searcher::PROGRESS_LINES * 2 + 1);
+    let mut sink = Events::default();
+    Searcher::new()
+        .search_reader(&matcher, big.as_bytes(), &mut sink)
+        .unwrap();
+    assert_eq!(
+        vec![searcher::PROGRESS_LINES, searcher::PROGRESS_LINES * 2],
+        sink.progress
+    );
+}
+
+#[test]
+fn it_filters_walked_files() {
+    let root = common::temp_tree(
+        "filter",
+        &[
+            (".hidden.rs", "fn hidden() {}\n"),
+            (".config/app.toml", "x = 1\n"),
+            ("big.log", &"x".repeat(2048)),
+            ("main.rs", "fn main() {}\n"),
+            ("notes.txt", "todo\n"),
+            ("src/lib.rs", "pub fn lib() {}\n"),
+            ("src/lib.c", "int main;\n"),
+            ("target/gen.rs", "// generated\n"),
+        ],
+    );
+    let walk = |list: &[&str]| -> Vec<String> {
+        let config = Config::new(args(&[&["x"], list].concat())).unwrap();
+        Walk::new(&root)
+            .with_filter(Filter::from_config(&config))
+            .map(|p| {
+                let p = p.unwrap();
+                p.strip_prefix(&root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect()
+    };
+
+    assert_eq!(
+        vec![
+            "big.log",
+            "main.rs",
+            "notes.txt",
+            "src/lib.c",
+            "src/lib.rs",
+            "target/gen.rs"
+        ],
+        walk(&[])
+    );
+    assert_eq!(
+        vec![".config/app.toml", ".hidden.rs", "main.rs", "src/lib.rs"],
+        walk(&[
+            "--hidden",
+            "-t",
+            "rust",
+            "-t",
+            "toml",
+            "--exclude",
+            "target"
+        ])
+    );
+    assert_eq!(
+        vec!["main.rs", "src/lib.c"],
+        walk(&[
+            "--include",
+            "*.rs",
+            "--include",
+            "src/*.c",
+            "--exclude",
+            "src/*.rs",
+            "--exclude",
+            "gen.*"
+        ])
+    );
+    assert_eq!(
+        vec!["notes.txt"],
+        walk(&["--include=*.txt", "-t", "txt", "-t", "rust"])
+    );
+    assert_eq!(
+        vec![
+            "main.rs",
+            "notes.txt",
+            "src/lib.c",
+            "src/lib.rs",
+            "target/gen.rs"
+        ],
+        walk(&["--max-filesize", "1K"])
+    );
+
+    assert_eq!(
+        Some(3 << 20),
+        Config::new(args(&["--max-filesize=3M", "x"]))
+            .unwrap()
+            .max_filesize
+    );
+    assert!(matches!(
+        Config::new(args(&["--max-filesize", "3X", "x"])),
+        Err(ConfigError::InvalidValue { .. })
+    ));
+    let err = Config::new(args(&["-t", "cobol", "x"])).unwrap_err();
+    assert_eq!(ConfigError::UnknownType(String::from("cobol")), err);
+    assert!(err.to_string().contains("rust"));
+}
+
+#[cfg(unix)]
+#[test]
+fn it_follows_symlinks_on_request() {
+    use std::os::unix::fs::symlink;
+
+    let root = common::temp_tree("follow", &[("a/file.txt", "x\n"), ("b/other.txt", "x\n")]);
+    symlink(root.join("b"), root.join("a/link")).unwrap();
+    symlink(root.join("a"), root.join("a/loop")).unwrap();
+    symlink(root.join("b/other.txt"), root.join("a/file-link.txt")).unwrap();
+    let walk = |filter: Filter| -> Vec<String> {
+        Walk::new(root.join("a"))
+            .with_filter(filter)
+            .map(|p| match p {
+                Ok(p) => p
+                    .strip_prefix(&root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                Err(e) => format!("error: {}", e.to_string().split(':').next().unwrap()),
+            })
+            .collect()
+    };
+
+    assert_eq!(vec!["a/file-link.txt", "a/file.txt"], walk(Filter::new()));
+    assert_eq!(
+        vec![
+            "a/file-link.txt",
+            "a/file.txt",
+            "a/link/other.txt",
+            "error: file system loop found"
+        ],
+        walk(Filter::new().follow_links(true))
     );
 }
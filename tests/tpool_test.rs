@@ -0,0 +1,623 @@
+use std::{
+    panic,
+    sync::{mpsc, Arc, Barrier, Once},
+    time::Duration,
+};
+
+use learning_rust::tpool::{
+    event::Event,
+    task::{TaskError, TaskHandle},
+    ExecuteError, JobPanic, Overflow, ThreadPool,
+};
+
+/// 测试里故意 panic 的 job 不往 stderr 输出 backtrace
+fn quiet_panics() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let worker = std::thread::current()
+                .name()
+                .is_some_and(|name| name.starts_with("tpool-worker"));
+            if !worker {
+                default(info);
+            }
+        }));
+    });
+}
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn it_survives_panicking_jobs() {
+    quiet_panics();
+    let pool = ThreadPool::new(2);
+    let (panics_tx, panics_rx) = mpsc::channel();
+    pool.set_panic_hook(move |panic: &JobPanic| panics_tx.send(panic.clone()).unwrap());
+
+    for i in 0..4 {
+        pool.execute(move || panic!("job {} failed", i));
+    }
+    pool.execute(|| std::panic::panic_any(42));
+
+    let mut messages: Vec<String> = (0..5)
+        .map(|_| panics_rx.recv_timeout(TIMEOUT).unwrap().message)
+        .collect();
+    messages.sort();
+    assert_eq!(
+        vec![
+            "Box<dyn Any>",
+            "job 0 failed",
+            "job 1 failed",
+            "job 2 failed",
+            "job 3 failed"
+        ],
+        messages
+    );
+    assert_eq!(5, pool.panic_count());
+    assert_eq!(0, pool.respawn_count());
+
+    // 两个 worker 都还活着：两个 job 要同时执行才能通过 barrier
+    let barrier = Arc::new(Barrier::new(3));
+    for _ in 0..2 {
+        let barrier = Arc::clone(&barrier);
+        pool.execute(move || {
+            barrier.wait();
+        });
+    }
+    barrier.wait();
+}
+
+#[test]
+fn it_respawns_dead_workers() {
+    quiet_panics();
+    let pool = ThreadPool::new(3);
+    // hook 本身 panic 会带走 worker 线程
+    pool.set_panic_hook(|_| panic!("hook failed"));
+    for _ in 0..3 {
+        pool.execute(|| panic!("job failed"));
+    }
+
+    let barrier = Arc::new(Barrier::new(4));
+    let (tx, rx) = mpsc::channel();
+    for i in 0..3 {
+        let (barrier, tx) = (Arc::clone(&barrier), tx.clone());
+        pool.execute(move || {
+            barrier.wait();
+            tx.send(i).unwrap();
+        });
+    }
+    barrier.wait();
+    let mut done: Vec<i32> = (0..3).map(|_| rx.recv_timeout(TIMEOUT).unwrap()).collect();
+    done.sort();
+    assert_eq!(vec![0, 1, 2], done);
+    assert_eq!(3, pool.panic_count());
+    assert_eq!(3, pool.respawn_count());
+
+    // drop 时要等到重新启动的 worker 也退出
+    drop(pool);
+}
+
+#[test]
+fn it_returns_results_through_task_handles() {
+    quiet_panics();
+    let pool = ThreadPool::new(2);
+    let handles: Vec<TaskHandle<usize>> = (0..20).map(|i| pool.spawn(move || i * i)).collect();
+    let squares: Vec<usize> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    assert_eq!((0..20).map(|i| i * i).collect::<Vec<_>>(), squares);
+
+    // job 等到 `go` 之后才返回
+    let (go, wait) = mpsc::channel::<()>();
+    let mut slow = pool.spawn(move || {
+        wait.recv().unwrap();
+        String::from("done")
+    });
+    assert_eq!(None, slow.try_join());
+    assert_eq!(None, slow.join_timeout(Duration::from_millis(20)));
+    go.send(()).unwrap();
+    assert_eq!(Some(Ok(String::from("done"))), slow.join_timeout(TIMEOUT));
+
+    let failed = pool.spawn(|| -> u8 { panic!("bad input {}", 7) });
+    let err = failed.join().unwrap_err();
+    assert_eq!(TaskError::Panicked(String::from("bad input 7")), err);
+    assert_eq!("task panicked: bad input 7", err.to_string());
+    // 句柄先拿到错误，worker 随后才把 panic 交给 hook
+    while pool.panic_count() == 0 {
+        std::thread::yield_now();
+    }
+    assert_eq!(1, pool.panic_count());
+
+    let mut done = pool.spawn(|| 1);
+    let mut polled = None;
+    while polled.is_none() {
+        polled = done.try_join();
+        std::thread::yield_now();
+    }
+    assert_eq!(Some(Ok(1)), polled);
+    let again = panic::catch_unwind(panic::AssertUnwindSafe(|| done.try_join()));
+    assert!(again.is_err());
+}
+
+#[test]
+fn it_keeps_nested_jobs_local() {
+    let pool = Arc::new(ThreadPool::new(2));
+    // 让另一个 worker 一直忙着，偷不了 job
+    let (release, blocked) = mpsc::channel::<()>();
+    let (started_tx, started) = mpsc::channel();
+    pool.execute(move || {
+        started_tx.send(()).unwrap();
+        blocked.recv().unwrap();
+    });
+    started.recv_timeout(TIMEOUT).unwrap();
+
+    let (tx, rx) = mpsc::channel();
+    let inner = Arc::clone(&pool);
+    pool.execute(move || {
+        let parent = std::thread::current().name().unwrap().to_string();
+        for i in 0..5 {
+            let (tx, parent) = (tx.clone(), parent.clone());
+            inner.execute(move || {
+                let same = std::thread::current().name() == Some(parent.as_str());
+                tx.send((i, same)).unwrap();
+            });
+        }
+    });
+    // 子 job 在父 job 的 worker 上按后进先出的顺序执行
+    let order: Vec<(i32, bool)> = (0..5).map(|_| rx.recv_timeout(TIMEOUT).unwrap()).collect();
+    assert_eq!(
+        vec![(4, true), (3, true), (2, true), (1, true), (0, true)],
+        order
+    );
+    release.send(()).unwrap();
+}
+
+#[test]
+fn it_runs_nested_jobs_before_shutting_down() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 每个 job 再提交两个子 job，直到深度为 0
+    fn fan_out(pool: &'static ThreadPool, depth: u32, count: Arc<AtomicUsize>) {
+        count.fetch_add(1, Ordering::SeqCst);
+        if depth > 0 {
+            for _ in 0..2 {
+                let count = Arc::clone(&count);
+                pool.execute(move || fan_out(pool, depth - 1, count));
+            }
+        }
+    }
+
+    // 线程池在 job 里被用到，要活得比所有 job 都久
+    let pool: &'static ThreadPool = Box::leak(Box::new(ThreadPool::new(4)));
+    let count = Arc::new(AtomicUsize::new(0));
+    let (tx, rx) = mpsc::channel();
+    let (c, done) = (Arc::clone(&count), tx.clone());
+    pool.execute(move || {
+        fan_out(pool, 12, c);
+        done.send(()).unwrap();
+    });
+    rx.recv_timeout(TIMEOUT).unwrap();
+    let expected = (1 << 13) - 1;
+    let started = std::time::Instant::now();
+    while count.load(Ordering::SeqCst) < expected && started.elapsed() < TIMEOUT {
+        std::thread::yield_now();
+    }
+    assert_eq!(expected, count.load(Ordering::SeqCst));
+
+    // drop 要等到队列里的 job 都执行完
+    let pool = ThreadPool::new(2);
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..8 {
+        let count = Arc::clone(&count);
+        pool.execute(move || {
+            std::thread::sleep(Duration::from_millis(5));
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+    drop(pool);
+    assert_eq!(8, count.load(Ordering::SeqCst));
+}
+
+/// 占住线程池里唯一的 worker，直到发送 `()`
+fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+    let (release, blocked) = mpsc::channel::<()>();
+    let (started_tx, started) = mpsc::channel();
+    pool.execute(move || {
+        started_tx.send(()).unwrap();
+        let _ = blocked.recv();
+    });
+    started.recv_timeout(TIMEOUT).unwrap();
+    release
+}
+
+#[test]
+fn it_drains_queued_jobs_on_shutdown() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pool = ThreadPool::new(1);
+    let (events_tx, events) = mpsc::channel();
+    pool.set_log_hook(move |event: &Event| {
+        let _ = events_tx.send(event.clone());
+    });
+    let release = block_worker(&pool);
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let queued: Vec<TaskHandle<()>> = (0..5)
+        .map(|_| {
+            let count = Arc::clone(&count);
+            pool.spawn(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+    pool.shutdown();
+    pool.shutdown();
+    let late = pool.spawn(|| ());
+    assert_eq!(Err(TaskError::Canceled), late.join());
+    assert!(!pool.join_timeout(Duration::from_millis(20)));
+
+    release.send(()).unwrap();
+    assert!(pool.join_timeout(TIMEOUT));
+    assert_eq!(5, count.load(Ordering::SeqCst));
+    for handle in queued {
+        assert_eq!(Ok(()), handle.join());
+    }
+
+    let events: Vec<Event> = events.try_iter().collect();
+    assert_eq!(1, events.iter().filter(|e| **e == Event::Shutdown).count());
+    assert!(events.contains(&Event::JobRejected));
+    assert_eq!(Some(&Event::WorkerExited { worker: 0 }), events.last());
+    assert_eq!(
+        "Worker 0 was told to terminate.",
+        events.last().unwrap().to_string()
+    );
+}
+
+#[test]
+fn it_returns_pending_jobs_on_shutdown_now() {
+    let pool = ThreadPool::new(1);
+    let (events_tx, events) = mpsc::channel();
+    pool.set_log_hook(move |event: &Event| {
+        let _ = events_tx.send(event.clone());
+    });
+    let release = block_worker(&pool);
+
+    let handles: Vec<TaskHandle<i32>> = (0..3).map(|i| pool.spawn(move || i)).collect();
+    let (ran_tx, ran) = mpsc::channel();
+    pool.execute(move || ran_tx.send(()).unwrap());
+
+    let pending = pool.shutdown_now();
+    assert_eq!(4, pending.len());
+    release.send(()).unwrap();
+    assert!(pool.join_timeout(TIMEOUT));
+
+    // 取回的 job 还可以由调用者自己执行
+    let mut pending = pending.into_iter();
+    pending.next_back().unwrap()();
+    ran.recv_timeout(TIMEOUT).unwrap();
+    drop(pending);
+    for handle in handles {
+        assert_eq!(Err(TaskError::Canceled), handle.join());
+    }
+
+    pool.execute(|| unreachable!());
+    let events: Vec<Event> = events.try_iter().collect();
+    assert!(events.contains(&Event::ShutdownNow { dropped: 4 }));
+    assert_eq!(Some(&Event::JobRejected), events.last());
+    assert!(!events.contains(&Event::Shutdown));
+}
+
+#[test]
+fn it_applies_overflow_policies() {
+    // 一个 worker 被占住，队列里最多两个 job
+    for overflow in [Overflow::Reject, Overflow::DropOldest, Overflow::CallerRuns] {
+        let pool = ThreadPool::bounded(1, 2, overflow);
+        let (events_tx, events) = mpsc::channel();
+        pool.set_log_hook(move |event: &Event| {
+            let _ = events_tx.send(event.clone());
+        });
+        let release = block_worker(&pool);
+
+        let caller = std::thread::current().id();
+        let handles: Vec<TaskHandle<bool>> = (0..4)
+            .map(|_| pool.spawn(move || std::thread::current().id() == caller))
+            .collect();
+        release.send(()).unwrap();
+        let results: Vec<Result<bool, TaskError>> = handles.into_iter().map(|h| h.join()).collect();
+        let events: Vec<Event> = events.try_iter().collect();
+        match overflow {
+            Overflow::Reject => {
+                let canceled = Err(TaskError::Canceled);
+                assert_eq!(
+                    vec![Ok(false), Ok(false), canceled.clone(), canceled],
+                    results
+                );
+                assert_eq!(2, events.iter().filter(|e| **e == Event::QueueFull).count());
+            }
+            Overflow::DropOldest => {
+                let canceled = Err(TaskError::Canceled);
+                assert_eq!(
+                    vec![canceled.clone(), canceled, Ok(false), Ok(false)],
+                    results
+                );
+                assert_eq!(
+                    2,
+                    events.iter().filter(|e| **e == Event::JobDropped).count()
+                );
+            }
+            Overflow::CallerRuns => {
+                assert_eq!(vec![Ok(false), Ok(false), Ok(true), Ok(true)], results);
+            }
+            Overflow::Block => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn it_blocks_when_the_queue_is_full() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pool = Arc::new(ThreadPool::bounded(1, 2, Overflow::Block));
+    let release = block_worker(&pool);
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..2 {
+        let count = Arc::clone(&count);
+        pool.execute(move || {
+            count.fetch_add(1, Ordering::SeqCst);
+        });
+    }
+
+    let err = pool.try_execute(|| {}).unwrap_err();
+    assert!(matches!(err, ExecuteError::Full(_)));
+    assert_eq!("thread pool queue is full", err.to_string());
+
+    // 第三个 job 要等 worker 腾出位置才能提交
+    let (submitted_tx, submitted) = mpsc::channel();
+    let submitter = {
+        let (pool, count) = (Arc::clone(&pool), Arc::clone(&count));
+        std::thread::spawn(move || {
+            pool.execute(move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            });
+            submitted_tx.send(()).unwrap();
+        })
+    };
+    assert!(submitted.recv_timeout(Duration::from_millis(50)).is_err());
+    release.send(()).unwrap();
+    submitted.recv_timeout(TIMEOUT).unwrap();
+    submitter.join().unwrap();
+
+    // 取回的 job 照样可以执行
+    let (ran_tx, ran) = mpsc::channel();
+    pool.shutdown();
+    let err = pool
+        .try_execute(move || ran_tx.send(()).unwrap())
+        .unwrap_err();
+    assert_eq!("thread pool is shut down", err.to_string());
+    err.into_job()();
+    ran.recv_timeout(TIMEOUT).unwrap();
+    assert!(pool.join_timeout(TIMEOUT));
+    assert_eq!(3, count.load(Ordering::SeqCst));
+}
+
+/// 最多等待 `timeout`，`cond` 返回 `true` 时立即返回 `true`
+fn eventually(timeout: Duration, cond: impl Fn() -> bool) -> bool {
+    let started = std::time::Instant::now();
+    while !cond() {
+        if started.elapsed() > timeout {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    true
+}
+
+#[test]
+fn it_grows_on_demand_and_retires_idle_workers() {
+    let pool = ThreadPool::builder()
+        .min_threads(1)
+        .max_threads(4)
+        .keep_alive(Duration::from_millis(50))
+        .build();
+    let (events_tx, events) = mpsc::channel();
+    pool.set_log_hook(move |event: &Event| {
+        let _ = events_tx.send(event.clone());
+    });
+    assert_eq!(1, pool.thread_count());
+
+    // 5 个 job 都等着放行，只有 4 个能同时开始
+    let (started_tx, started) = mpsc::channel();
+    let mut releases = Vec::new();
+    for i in 0..5 {
+        let (release, blocked) = mpsc::channel::<()>();
+        let started_tx = started_tx.clone();
+        pool.execute(move || {
+            started_tx.send(i).unwrap();
+            let _ = blocked.recv();
+        });
+        releases.push(release);
+    }
+    // 哪 4 个先开始不一定：job 可能被搬到了某个正忙着的 worker 的队列里
+    let mut running: Vec<i32> = (0..4)
+        .map(|_| started.recv_timeout(TIMEOUT).unwrap())
+        .collect();
+    assert_eq!(4, pool.thread_count());
+    assert!(started.recv_timeout(Duration::from_millis(20)).is_err());
+
+    drop(releases);
+    running.push(started.recv_timeout(TIMEOUT).unwrap());
+    running.sort();
+    assert_eq!(vec![0, 1, 2, 3, 4], running);
+    // 多出来的 3 个 worker 空闲 50ms 以后退出
+    assert!(eventually(TIMEOUT, || pool.thread_count() == 1));
+    // 但不会少于 `min_threads`
+    assert!(!eventually(Duration::from_millis(100), || {
+        pool.thread_count() < 1
+    }));
+
+    let events: Vec<Event> = events.try_iter().collect();
+    let count = |f: fn(&Event) -> bool| events.iter().filter(|e| f(e)).count();
+    assert_eq!(3, count(|e| matches!(e, Event::WorkerStarted { .. })));
+    assert_eq!(3, count(|e| matches!(e, Event::WorkerRetired { .. })));
+
+    // 空闲的 worker 够用时不再启动新的
+    let (done_tx, done) = mpsc::channel();
+    pool.execute(move || done_tx.send(()).unwrap());
+    done.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(1, pool.thread_count());
+}
+
+#[test]
+fn it_resizes_without_losing_jobs() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 每个 job 再提交两个子 job，直到深度为 0
+    fn fan_out(pool: &'static ThreadPool, depth: u32, count: Arc<AtomicUsize>) {
+        std::thread::sleep(Duration::from_micros(50));
+        count.fetch_add(1, Ordering::SeqCst);
+        if depth > 0 {
+            for _ in 0..2 {
+                let count = Arc::clone(&count);
+                pool.execute(move || fan_out(pool, depth - 1, count));
+            }
+        }
+    }
+
+    let pool: &'static ThreadPool = Box::leak(Box::new(ThreadPool::new(4)));
+    let count = Arc::new(AtomicUsize::new(0));
+    for _ in 0..4 {
+        let count = Arc::clone(&count);
+        pool.execute(move || fan_out(pool, 8, count));
+    }
+    std::thread::sleep(Duration::from_millis(5));
+    pool.resize(1, 1);
+
+    let expected = 4 * ((1 << 9) - 1);
+    assert!(eventually(TIMEOUT, || count.load(Ordering::SeqCst) == expected));
+    assert!(eventually(TIMEOUT, || pool.thread_count() == 1));
+
+    pool.resize(3, 3);
+    assert_eq!(3, pool.thread_count());
+    let handles: Vec<TaskHandle<usize>> = (0..30).map(|i| pool.spawn(move || i)).collect();
+    let sum: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+    assert_eq!(435, sum);
+
+    pool.shutdown();
+    assert!(pool.join_timeout(TIMEOUT));
+    assert_eq!(0, pool.thread_count());
+}
+
+#[test]
+fn it_does_not_strand_jobs_when_idle_workers_retire() {
+    let keep_alive = Duration::from_millis(3);
+    let pool = ThreadPool::builder()
+        .min_threads(0)
+        .max_threads(1)
+        .keep_alive(keep_alive)
+        .build();
+
+    // 在唯一的 worker 空闲到期前后提交，job 不能留在没人执行的队列里
+    for i in 0..300u32 {
+        let mut handle = pool.spawn(move || i);
+        assert_eq!(
+            Some(Ok(i)),
+            handle.join_timeout(TIMEOUT),
+            "job {} was stranded",
+            i
+        );
+        // 2ms 到 4ms 之间，跨过 keep_alive 到期的时刻
+        let jitter = Duration::from_micros(u64::from(i % 9) * 250);
+        std::thread::sleep(keep_alive - Duration::from_millis(1) + jitter);
+    }
+}
+
+#[test]
+fn it_borrows_data_in_scoped_jobs() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pool = ThreadPool::new(4);
+    let mut data: Vec<u64> = (1..=1000).collect();
+    let visited = AtomicUsize::new(0);
+
+    // 并行地处理每一段，不需要 `Arc`
+    let chunks = pool.scope(|s| {
+        let mut chunks = 0;
+        for chunk in data.chunks_mut(64) {
+            let visited = &visited;
+            s.spawn(move || {
+                for n in chunk.iter_mut() {
+                    *n *= 2;
+                }
+                visited.fetch_add(chunk.len(), Ordering::SeqCst);
+            });
+            chunks += 1;
+        }
+        chunks
+    });
+    assert_eq!(16, chunks);
+    assert_eq!(1000, visited.load(Ordering::SeqCst));
+    assert_eq!((1..=1000).map(|n| n * 2).collect::<Vec<u64>>(), data);
+
+    // job 里还可以再提交 job
+    let words = ["alpha", "beta", "gamma"];
+    let lengths = std::sync::Mutex::new(Vec::new());
+    pool.scope(|s| {
+        for word in &words {
+            let lengths = &lengths;
+            s.spawn(move || {
+                s.spawn(move || lengths.lock().unwrap().push(word.len()));
+            });
+        }
+    });
+    let mut lengths = lengths.into_inner().unwrap();
+    lengths.sort();
+    assert_eq!(vec![4, 5, 5], lengths);
+}
+
+#[test]
+fn it_runs_scopes_inside_jobs() {
+    // 只有一个 worker：它在 scope 里等待时必须自己执行这些 job
+    let pool: &'static ThreadPool = Box::leak(Box::new(ThreadPool::new(1)));
+    let mut handle = pool.spawn(move || {
+        let numbers: Vec<u32> = (1..=100).collect();
+        let sums = std::sync::Mutex::new(Vec::new());
+        pool.scope(|s| {
+            for chunk in numbers.chunks(10) {
+                let sums = &sums;
+                s.spawn(move || sums.lock().unwrap().push(chunk.iter().sum::<u32>()));
+            }
+        });
+        sums.into_inner().unwrap().iter().sum::<u32>()
+    });
+    assert_eq!(Some(Ok(5050)), handle.join_timeout(TIMEOUT));
+}
+
+#[test]
+fn it_propagates_scoped_panics() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    quiet_panics();
+    let pool = ThreadPool::new(2);
+    let finished = AtomicUsize::new(0);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        pool.scope(|s| {
+            s.spawn(|| panic!("scoped boom"));
+            for _ in 0..4 {
+                s.spawn(|| {
+                    std::thread::sleep(Duration::from_millis(5));
+                    finished.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+        })
+    }));
+    let payload = result.unwrap_err();
+    assert_eq!(Some(&"scoped boom"), payload.downcast_ref::<&str>());
+    // panic 之前所有 job 都已经结束了
+    assert_eq!(4, finished.load(Ordering::SeqCst));
+    assert_eq!(0, pool.panic_count());
+
+    // 线程池关闭以后在当前线程里执行
+    pool.shutdown();
+    let mut ran_on = None;
+    pool.scope(|s| s.spawn(|| ran_on = Some(std::thread::current().id())));
+    assert_eq!(Some(std::thread::current().id()), ran_on);
+}